@@ -1,17 +1,26 @@
 #!/bin/bash
 
-COMMAND="cargo run --"
+# The tests read ./sql.db, point storage.path at the current directory instead of the
+# default /var/sota
+CONFIG_DIR=$(mktemp -d)
+trap 'rm -rf "$CONFIG_DIR"' EXIT
+cat > "$CONFIG_DIR/10-e2e.toml" <<EOF
+[storage]
+path = "$PWD"
+EOF
+
+COMMAND="cargo run -- --config $CONFIG_DIR"
 
 options=(
     "--name-only"
-    "--tls-creds"
+    "--tls-creds --show-secrets"
     "--tls-root-ca"
     "--tls-cert"
-    "--tls-prv-key"
-    "--ecu-keys"
+    "--tls-prv-key --show-secrets"
+    "--ecu-keys --show-secrets"
     "--ecu-keyid"
     "--ecu-pub-key"
-    "--ecu-prv-key"
+    "--ecu-prv-key --show-secrets"
     "--secondary-keys"
     "--image-root"
     "--image-timestamp"
@@ -19,8 +28,7 @@ options=(
     "--image-targets"
     "--director-root"
     "--director-targets"
-    "--root-version"
-    "--allow-migrate"
+    "--image-root --root-version 1"
     "--wait-until-provisioned"
 )
 
@@ -0,0 +1,213 @@
+use crate::error::{Error, Result};
+use crate::zeroizing_writer::{wipe_json, ZeroizingWriter};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde_json::{Map, Value};
+use std::fs;
+use std::io::{ErrorKind, Write};
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use zeroize::Zeroizing;
+
+// Mode of the files in an archive, private ones are only readable by their owner
+const FILE_MODE: u32 = 0o644;
+const PRIVATE_FILE_MODE: u32 = 0o600;
+const DIRECTORY_MODE: u32 = 0o755;
+
+struct Entry {
+    path: PathBuf,
+    content: Zeroizing<Vec<u8>>,
+    private: bool,
+}
+
+/// Set of files written at once to a directory, a tarball or a JSON bundle. The output format
+/// follows the file name: .tar.gz and .tgz are compressed tarballs, .tar plain ones, .json a
+/// single document mapping each path to the file content, anything else is a directory.
+/// Existing outputs are never overwritten.
+#[derive(Default)]
+pub struct Archive {
+    entries: Vec<Entry>,
+}
+
+impl Archive {
+    /// Empty archive.
+    pub fn new() -> Self {
+        Archive::default()
+    }
+
+    /// Adds a file readable by everyone.
+    pub fn add(&mut self, path: impl Into<PathBuf>, content: impl Into<Vec<u8>>) {
+        self.push(path.into(), Zeroizing::new(content.into()), false);
+    }
+
+    /// Private files are written readable by their owner only and wiped from memory on drop
+    pub fn add_private(&mut self, path: impl Into<PathBuf>, content: &[u8]) {
+        self.push(path.into(), Zeroizing::new(content.to_vec()), true);
+    }
+
+    /// Paths of the files added so far, in the order they were added.
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.entries.iter().map(|entry| entry.path.as_path())
+    }
+
+    /// Writes every file to the output, in the format its name selects.
+    pub fn write(&self, output: &Path) -> Result<()> {
+        self.check_paths()?;
+        let name = output.to_string_lossy();
+        if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            let file = self.create_file(output)?;
+            let encoder = GzEncoder::new(file, Compression::default());
+            let encoder = self
+                .write_tar(encoder)
+                .map_err(|error| Self::io_error(output, error))?;
+            encoder
+                .finish()
+                .map_err(|error| Self::io_error(output, error))?;
+            Ok(())
+        } else if name.ends_with(".json") {
+            // Private files are embedded in the document, which is wiped once written
+            let mut document = self.to_json();
+            let mut buffer = ZeroizingWriter::new();
+            let serialized = serde_json::to_writer_pretty(&mut buffer, &document);
+            wipe_json(&mut document);
+            serialized.map_err(|error| Self::io_error(output, error.into()))?;
+
+            let mut file = self.create_file(output)?;
+            file.write_all(&buffer)
+                .and_then(|()| file.write_all(b"\n"))
+                .map_err(|error| Self::io_error(output, error))?;
+            Ok(())
+        } else if name.ends_with(".tar") {
+            let file = self.create_file(output)?;
+            self.write_tar(file)
+                .map_err(|error| Self::io_error(output, error))?;
+            Ok(())
+        } else {
+            self.write_directory(output)
+        }
+    }
+
+    /// JSON files are embedded as they are, other files as text. The value holds the private
+    /// files too, see wipe_json.
+    pub fn to_json(&self) -> Value {
+        let files: Map<String, Value> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                let content = serde_json::from_slice(&entry.content).unwrap_or_else(|_| {
+                    Value::String(String::from_utf8_lossy(&entry.content).into_owned())
+                });
+                (entry.path.display().to_string(), content)
+            })
+            .collect();
+        Value::Object(files)
+    }
+
+    fn push(&mut self, path: PathBuf, content: Zeroizing<Vec<u8>>, private: bool) {
+        self.entries.retain(|entry| entry.path != path);
+        self.entries.push(Entry {
+            path,
+            content,
+            private,
+        });
+    }
+
+    fn write_tar<W: Write>(&self, writer: W) -> std::io::Result<W> {
+        let mtime = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or_default();
+
+        let mut builder = tar::Builder::new(writer);
+        for entry in &self.entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(entry.content.len() as u64);
+            header.set_mode(if entry.private {
+                PRIVATE_FILE_MODE
+            } else {
+                FILE_MODE
+            });
+            header.set_mtime(mtime);
+            header.set_entry_type(tar::EntryType::Regular);
+            builder.append_data(&mut header, &entry.path, entry.content.as_slice())?;
+        }
+        builder.into_inner()
+    }
+
+    fn write_directory(&self, output: &Path) -> Result<()> {
+        let not_empty = match fs::read_dir(output) {
+            Ok(mut entries) => entries.next().is_some(),
+            Err(e) if e.kind() == ErrorKind::NotFound => false,
+            Err(error) => return Err(Self::io_error(output, error)),
+        };
+        if not_empty || output.is_file() {
+            return Err(Self::io_error(output, ErrorKind::AlreadyExists.into()));
+        }
+
+        for entry in &self.entries {
+            let path = output.join(&entry.path);
+            if let Some(parent) = path.parent() {
+                fs::DirBuilder::new()
+                    .recursive(true)
+                    .mode(DIRECTORY_MODE)
+                    .create(parent)
+                    .map_err(|error| Self::io_error(parent, error))?;
+            }
+            let mode = if entry.private {
+                PRIVATE_FILE_MODE
+            } else {
+                FILE_MODE
+            };
+            let mut file = fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .mode(mode)
+                .open(&path)
+                .map_err(|error| Self::io_error(&path, error))?;
+            file.write_all(&entry.content)
+                .map_err(|error| Self::io_error(&path, error))?;
+        }
+        Ok(())
+    }
+
+    // Every file has to end up inside the output, whatever the format
+    fn check_paths(&self) -> Result<()> {
+        for entry in &self.entries {
+            let inside = entry.path.components().next().is_some()
+                && entry
+                    .path
+                    .components()
+                    .all(|component| matches!(component, Component::Normal(_)));
+            if !inside {
+                return Err(Self::io_error(
+                    &entry.path,
+                    std::io::Error::new(ErrorKind::InvalidInput, "path leaves the output"),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // A tarball holding private files is itself private
+    fn create_file(&self, path: &Path) -> Result<fs::File> {
+        let mode = if self.entries.iter().any(|entry| entry.private) {
+            PRIVATE_FILE_MODE
+        } else {
+            FILE_MODE
+        };
+        fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(mode)
+            .open(path)
+            .map_err(|error| Self::io_error(path, error))
+    }
+
+    fn io_error(path: &Path, error: std::io::Error) -> Error {
+        Error::Io {
+            path: path.to_path_buf(),
+            error,
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use serde_json::Value;
+use std::fmt;
+
+// Serializer for the OLPC canonical JSON form used to sign TUF metadata and to compute key
+// IDs: no insignificant whitespace, object keys sorted by their bytes, non-ASCII characters
+// written as is and no floating point numbers.
+//
+/// Strict OLPC only escapes '"' and '\' and writes control characters raw. aktualizr and the
+/// Uptane backend escape control characters like regular JSON does, so a PEM key with its
+/// newlines only gets the key ID found in root.json if we do the same.
+pub struct CanonicalJson;
+
+impl CanonicalJson {
+    /// Canonical form of the value, as signed by aktualizr and the Uptane backend.
+    pub fn serialize(value: &Value) -> Result<String, CanonicalJsonError> {
+        let mut out = String::new();
+        Self::write_value(value, &mut out)?;
+        Ok(out)
+    }
+
+    /// Canonical form of a single string. Unlike serializing arbitrary JSON this cannot fail, so
+    /// documents made only of strings can be built from it without an error path
+    pub fn string(s: &str) -> String {
+        let mut out = String::new();
+        Self::write_string(s, &mut out);
+        out
+    }
+
+    fn write_value(value: &Value, out: &mut String) -> Result<(), CanonicalJsonError> {
+        match value {
+            Value::Null => out.push_str("null"),
+            Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+            Value::Number(n) => {
+                if n.is_f64() {
+                    return Err(CanonicalJsonError(format!(
+                        "floating point number {} is not allowed",
+                        n
+                    )));
+                }
+                out.push_str(&n.to_string());
+            }
+            Value::String(s) => Self::write_string(s, out),
+            Value::Array(items) => {
+                out.push('[');
+                for (index, item) in items.iter().enumerate() {
+                    if index > 0 {
+                        out.push(',');
+                    }
+                    Self::write_value(item, out)?;
+                }
+                out.push(']');
+            }
+            Value::Object(map) => {
+                let mut keys: Vec<&String> = map.keys().collect();
+                keys.sort_by(|a, b| a.as_bytes().cmp(b.as_bytes()));
+
+                out.push('{');
+                for (index, key) in keys.into_iter().enumerate() {
+                    if index > 0 {
+                        out.push(',');
+                    }
+                    Self::write_string(key, out);
+                    out.push(':');
+                    Self::write_value(&map[key], out)?;
+                }
+                out.push('}');
+            }
+        }
+        Ok(())
+    }
+
+    fn write_string(s: &str, out: &mut String) {
+        out.push('"');
+        for c in s.chars() {
+            match c {
+                '"' => out.push_str("\\\""),
+                '\\' => out.push_str("\\\\"),
+                '\u{08}' => out.push_str("\\b"),
+                '\u{0c}' => out.push_str("\\f"),
+                '\n' => out.push_str("\\n"),
+                '\r' => out.push_str("\\r"),
+                '\t' => out.push_str("\\t"),
+                c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+                c => out.push(c),
+            }
+        }
+        out.push('"');
+    }
+}
+
+/// Value that has no canonical form, such as a float.
+#[derive(Debug)]
+pub struct CanonicalJsonError(String);
+
+impl fmt::Display for CanonicalJsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "CanonicalJson Error: {}", self.0)
+    }
+}
+
+impl std::error::Error for CanonicalJsonError {}
@@ -0,0 +1,491 @@
+use log::{debug, trace};
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml::de::{DeTable, DeValue};
+
+/// Same search order as aktualizr: fragments in /etc override those shipped in /usr/lib
+pub const DEFAULT_CONFIG_DIRS: [&str; 2] = ["/usr/lib/sota/conf.d", "/etc/sota/conf.d"];
+
+const CONFIG_FILE_EXTENSION: &str = "toml";
+
+/// Placeholder for the values of secret options in redacted output
+pub const REDACTED: &str = "<redacted>";
+
+// Options known to hold secrets: the PKCS#12 bundle password and the PKCS#11 token PIN
+const SECRET_OPTIONS: [&str; 2] = ["provision.p12_password", "p11.pass"];
+
+// Words that mark any other option as secret, matched against the start of each word of its
+// name
+const SECRET_WORDS: [&str; 4] = ["pass", "pin", "secret", "token"];
+
+// Sections printed first, in this order, by the Display implementation
+const KNOWN_SECTIONS: [&str; 5] = ["storage", "provision", "uptane", "pacman", "import"];
+
+// aktualizr's built-in defaults for the options it reads from the known sections
+const DEFAULTS: [(&str, &str); 38] = [
+    ("storage.type", "sqlite"),
+    ("storage.path", "/var/sota"),
+    ("storage.sqldb_path", "sql.db"),
+    ("storage.uptane_metadata_path", "metadata"),
+    ("storage.uptane_private_key_path", "ecukey.der"),
+    ("storage.uptane_public_key_path", "ecukey.pub"),
+    ("storage.tls_cacert_path", "root.crt"),
+    ("storage.tls_pkey_path", "pkey.pem"),
+    ("storage.tls_clientcert_path", "client.pem"),
+    ("provision.server", ""),
+    ("provision.p12_password", ""),
+    ("provision.expiry_days", "36000"),
+    ("provision.provision_path", ""),
+    ("provision.device_id", ""),
+    ("provision.primary_ecu_serial", ""),
+    ("provision.primary_ecu_hardware_id", ""),
+    ("provision.ecu_registration_endpoint", ""),
+    ("provision.mode", "Default"),
+    ("uptane.polling_sec", "300"),
+    ("uptane.director_server", ""),
+    ("uptane.repo_server", ""),
+    ("uptane.key_source", "file"),
+    ("uptane.key_type", "RSA2048"),
+    ("uptane.force_install_completion", "false"),
+    ("uptane.secondary_config_file", ""),
+    ("uptane.secondary_preinstall_wait_sec", "600"),
+    ("pacman.type", "ostree"),
+    ("pacman.os", ""),
+    ("pacman.sysroot", ""),
+    ("pacman.ostree_server", ""),
+    ("pacman.packages_file", "/usr/package.manifest"),
+    ("pacman.fake_need_reboot", "false"),
+    ("import.base_path", "/var/sota/import"),
+    ("import.uptane_private_key_path", ""),
+    ("import.uptane_public_key_path", ""),
+    ("import.tls_cacert_path", ""),
+    ("import.tls_pkey_path", ""),
+    ("import.tls_clientcert_path", ""),
+];
+
+/// Value of a configuration option and where it was set.
+#[derive(Debug, Clone)]
+pub struct ConfigEntry {
+    /// Decoded for strings, the TOML literal for values of any other type
+    pub value: String,
+    /// Whether the value was a TOML string.
+    pub is_string: bool,
+    /// Configuration file that set the value.
+    pub source: PathBuf,
+    /// Line of the option in that file.
+    pub line: usize,
+}
+
+/// aktualizr configuration merged from its files, by dotted key such as `storage.path`.
+#[derive(Debug, Default)]
+pub struct Config {
+    entries: BTreeMap<String, ConfigEntry>,
+}
+
+impl Config {
+    /// Loads the given files and directories the way aktualizr does: every file is keyed by
+    /// its file name, so a fragment in a later directory replaces one with the same name in
+    /// an earlier directory, and the resulting set is applied in lexicographic order. Every
+    /// path must exist.
+    pub fn load<P: AsRef<Path>>(paths: &[P]) -> Result<Self, ConfigError> {
+        let mut files: BTreeMap<String, PathBuf> = BTreeMap::new();
+
+        for path in paths {
+            let path = path.as_ref();
+            if !path.exists() {
+                return Err(ConfigError(format!(
+                    "Config path {} does not exist",
+                    path.display()
+                )));
+            }
+
+            if path.is_dir() {
+                for file in Self::dir_entries(path)? {
+                    files.insert(Self::file_name(&file), file);
+                }
+            } else {
+                files.insert(Self::file_name(path), path.to_path_buf());
+            }
+        }
+
+        let mut config = Config::default();
+        for file in files.values() {
+            config.update_from_file(file)?;
+        }
+
+        Ok(config)
+    }
+
+    /// Unlike explicitly given paths, default directories that do not exist are skipped
+    pub fn load_default() -> Result<Self, ConfigError> {
+        let dirs: Vec<&str> = DEFAULT_CONFIG_DIRS
+            .into_iter()
+            .filter(|dir| {
+                let exists = Path::new(dir).exists();
+                if !exists {
+                    debug!("Config directory {} does not exist, skipping", dir);
+                }
+                exists
+            })
+            .collect();
+        Self::load(&dirs)
+    }
+
+    /// Returns the value set by the config files, or aktualizr's default if there is one
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .get(key)
+            .map(|entry| entry.value.as_str())
+            .or_else(|| Self::default_value(key))
+    }
+
+    /// Returns where the value of the key comes from, None meaning the built-in default
+    pub fn source(&self, key: &str) -> Option<&ConfigEntry> {
+        self.entries.get(key)
+    }
+
+    /// Directory of the filesystem storage, `storage.path`.
+    pub fn storage_path(&self) -> PathBuf {
+        PathBuf::from(self.get("storage.path").unwrap_or_default())
+    }
+
+    /// storage.sqldb_path is relative to storage.path unless it is absolute
+    pub fn sqldb_path(&self) -> PathBuf {
+        let sqldb_path = PathBuf::from(self.get("storage.sqldb_path").unwrap_or_default());
+        if sqldb_path.is_absolute() {
+            sqldb_path
+        } else {
+            self.storage_path().join(sqldb_path)
+        }
+    }
+
+    /// Same content as the Display output: every section maps each key to its value and origin
+    pub fn to_json(&self) -> Value {
+        let mut root = Map::new();
+        for (section, names) in self.sections() {
+            let mut options = Map::new();
+            for name in names {
+                let key = if section.is_empty() {
+                    name.clone()
+                } else {
+                    format!("{}.{}", section, name)
+                };
+                let value = self.get(&key).unwrap_or_default();
+                let origin = match self.source(&key) {
+                    Some(entry) => json!({
+                        "value": value,
+                        "source": entry.source.display().to_string(),
+                        "line": entry.line,
+                    }),
+                    None => json!({ "value": value, "source": "default" }),
+                };
+                options.insert(name, origin);
+            }
+
+            if section.is_empty() {
+                root.extend(options);
+            } else {
+                root.insert(section, Value::Object(options));
+            }
+        }
+        Value::Object(root)
+    }
+
+    /// Same as to_json, with the values of secret options such as passwords replaced
+    pub fn to_redacted_json(&self) -> Value {
+        let mut root = self.to_json();
+        if let Value::Object(entries) = &mut root {
+            for (name, entry) in entries.iter_mut() {
+                match entry {
+                    // A section, holding options
+                    Value::Object(options) if !options.contains_key("value") => {
+                        for (option_name, option) in options.iter_mut() {
+                            Self::redact(&format!("{}.{}", name, option_name), option);
+                        }
+                    }
+                    _ => Self::redact(name, entry),
+                }
+            }
+        }
+        root
+    }
+
+    /// Whether the option, by its full dotted key, holds a secret. Names are split into words
+    /// so that p12_password or api-token match, but not mapping
+    pub fn is_secret(key: &str) -> bool {
+        if SECRET_OPTIONS.contains(&key) {
+            return true;
+        }
+        let name = key.rsplit('.').next().unwrap_or(key).to_lowercase();
+        name.split(['_', '-'])
+            .any(|word| SECRET_WORDS.iter().any(|secret| word.starts_with(secret)))
+    }
+
+    fn redact(key: &str, option: &mut Value) {
+        let set = option["value"]
+            .as_str()
+            .is_some_and(|value| !value.is_empty());
+        if set && Self::is_secret(key) {
+            option["value"] = json!(REDACTED);
+        }
+    }
+
+    // The value of the key as it is written in TOML
+    fn value_literal(&self, key: &str) -> String {
+        match self.source(key) {
+            Some(entry) if !entry.is_string => entry.value.clone(),
+            Some(entry) => Self::quote(&entry.value),
+            // Defaults that read as booleans or integers are of that type in aktualizr
+            None => match Self::default_value(key).unwrap_or_default() {
+                value @ ("true" | "false") => value.to_string(),
+                value if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) => {
+                    value.to_string()
+                }
+                value => Self::quote(value),
+            },
+        }
+    }
+
+    fn default_value(key: &str) -> Option<&'static str> {
+        DEFAULTS
+            .iter()
+            .find(|(default_key, _)| *default_key == key)
+            .map(|(_, value)| *value)
+    }
+
+    // All keys, defaults included, grouped by section. Keys outside of any section come first
+    // so the output stays valid TOML, followed by the known sections.
+    fn sections(&self) -> Vec<(String, Vec<String>)> {
+        let mut sections: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        let keys = DEFAULTS
+            .iter()
+            .map(|(key, _)| *key)
+            .chain(self.entries.keys().map(String::as_str));
+
+        for key in keys {
+            let (section, name) = key.split_once('.').unwrap_or(("", key));
+            let names = sections.entry(section.to_string()).or_default();
+            if !names.iter().any(|existing| existing == name) {
+                names.push(name.to_string());
+            }
+        }
+
+        let mut ordered = Vec::new();
+        for section in std::iter::once("").chain(KNOWN_SECTIONS) {
+            if let Some(names) = sections.remove(section) {
+                ordered.push((section.to_string(), names));
+            }
+        }
+        ordered.extend(sections);
+        ordered
+    }
+
+    fn dir_entries(dir: &Path) -> Result<Vec<PathBuf>, ConfigError> {
+        let entries = fs::read_dir(dir).map_err(|e| {
+            ConfigError(format!("Unable to read directory {}: {}", dir.display(), e))
+        })?;
+
+        let mut files = Vec::new();
+        for entry in entries {
+            let path = entry
+                .map_err(|e| {
+                    ConfigError(format!("Unable to read directory {}: {}", dir.display(), e))
+                })?
+                .path();
+            if path.is_file()
+                && path.extension().and_then(|ext| ext.to_str()) == Some(CONFIG_FILE_EXTENSION)
+            {
+                files.push(path);
+            }
+        }
+
+        Ok(files)
+    }
+
+    fn file_name(path: &Path) -> String {
+        path.file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    fn update_from_file(&mut self, path: &Path) -> Result<(), ConfigError> {
+        debug!("Reading config file {}", path.display());
+
+        let content = fs::read_to_string(path)
+            .map_err(|e| ConfigError(format!("Unable to read {}: {}", path.display(), e)))?;
+        let document = DeTable::parse(&content)
+            .map_err(|e| ConfigError(format!("Unable to parse {}: {}", path.display(), e)))?;
+
+        self.update_from_table(path, &content, "", document.get_ref());
+        Ok(())
+    }
+
+    // Options of nested tables, from [section.sub] headers or inline tables, are keyed by their
+    // dotted path. String values are stored decoded, any other value as a TOML literal.
+    fn update_from_table(&mut self, path: &Path, content: &str, prefix: &str, table: &DeTable) {
+        for (key, value) in table {
+            let full_key = if prefix.is_empty() {
+                key.get_ref().to_string()
+            } else {
+                format!("{}.{}", prefix, key.get_ref())
+            };
+
+            let (value, is_string) = match value.get_ref() {
+                DeValue::Table(table) => {
+                    self.update_from_table(path, content, &full_key, table);
+                    continue;
+                }
+                DeValue::String(value) => (value.to_string(), true),
+                value => (Self::literal(value), false),
+            };
+            let line_number = content[..key.span().start].matches('\n').count() + 1;
+
+            trace!(
+                "{} = {} ({}:{})",
+                full_key,
+                value,
+                path.display(),
+                line_number
+            );
+            if let Some(previous) = self.entries.get(&full_key) {
+                debug!(
+                    "{} from {}:{} overrides {}:{}",
+                    full_key,
+                    path.display(),
+                    line_number,
+                    previous.source.display(),
+                    previous.line
+                );
+            }
+            self.entries.insert(
+                full_key,
+                ConfigEntry {
+                    value,
+                    is_string,
+                    source: path.to_path_buf(),
+                    line: line_number,
+                },
+            );
+        }
+    }
+
+    fn literal(value: &DeValue) -> String {
+        match value {
+            DeValue::String(value) => Self::quote(value),
+            DeValue::Integer(value) => value.to_string(),
+            DeValue::Float(value) => value.to_string(),
+            DeValue::Boolean(value) => value.to_string(),
+            DeValue::Datetime(value) => value.to_string(),
+            DeValue::Array(values) => {
+                let values: Vec<String> = values
+                    .iter()
+                    .map(|value| Self::literal(value.get_ref()))
+                    .collect();
+                format!("[{}]", values.join(", "))
+            }
+            DeValue::Table(table) => {
+                let entries: Vec<String> = table
+                    .iter()
+                    .map(|(key, value)| {
+                        format!(
+                            "{} = {}",
+                            Self::key(key.get_ref()),
+                            Self::literal(value.get_ref())
+                        )
+                    })
+                    .collect();
+                format!("{{ {} }}", entries.join(", "))
+            }
+        }
+    }
+
+    // Dotted keys of nested tables keep their dots, each part is quoted as needed
+    fn dotted_key(name: &str) -> String {
+        name.split('.').map(Self::key).collect::<Vec<_>>().join(".")
+    }
+
+    // Keys are left bare when TOML allows it
+    fn key(name: &str) -> String {
+        let bare = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if bare {
+            name.to_string()
+        } else {
+            Self::quote(name)
+        }
+    }
+
+    // TOML basic string, with quotes, backslashes and control characters escaped
+    fn quote(value: &str) -> String {
+        let mut quoted = String::with_capacity(value.len() + 2);
+        quoted.push('"');
+        for c in value.chars() {
+            match c {
+                '"' => quoted.push_str("\\\""),
+                '\\' => quoted.push_str("\\\\"),
+                '\n' => quoted.push_str("\\n"),
+                '\r' => quoted.push_str("\\r"),
+                '\t' => quoted.push_str("\\t"),
+                c if c.is_control() => quoted.push_str(&format!("\\u{:04X}", c as u32)),
+                c => quoted.push(c),
+            }
+        }
+        quoted.push('"');
+        quoted
+    }
+}
+
+// Prints the merged configuration as TOML, each key annotated with the file and line that
+// set it. The output can be read back as a config file.
+impl fmt::Display for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut first = true;
+        for (section, names) in self.sections() {
+            if !first {
+                writeln!(f)?;
+            }
+            first = false;
+
+            if !section.is_empty() {
+                writeln!(f, "[{}]", Self::key(&section))?;
+            }
+
+            for name in names {
+                let key = if section.is_empty() {
+                    name.clone()
+                } else {
+                    format!("{}.{}", section, name)
+                };
+                let origin = match self.source(&key) {
+                    Some(entry) => format!("{}:{}", entry.source.display(), entry.line),
+                    None => "default".to_string(),
+                };
+                writeln!(
+                    f,
+                    "{} = {} # {}",
+                    Self::dotted_key(&name),
+                    self.value_literal(&key),
+                    origin
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Configuration file that cannot be read or parsed.
+#[derive(Debug)]
+pub struct ConfigError(String);
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Config Error: {}", self.0)
+    }
+}
+
+impl std::error::Error for ConfigError {}
@@ -1,20 +1,45 @@
-use openssl::pkey::PKey;
+use openssl::bn::BigNum;
+use openssl::ecdsa::EcdsaSig;
+use openssl::nid::Nid;
+use openssl::pkey::{Id, PKey, Private};
 use openssl::rsa::Rsa;
-use ring::digest::{digest, SHA256};
+use openssl::sign::{RsaPssSaltlen, Signer};
+use ring::digest::{digest, SHA256, SHA512};
 use ring::signature::UnparsedPublicKey;
 use std::error::Error;
 use std::fmt;
 use std::str::FromStr;
+use zeroize::Zeroizing;
 
+/// Type of an Uptane key.
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum KeyType {
+    /// Ed25519 key.
     Ed25519,
+    /// 2048-bit RSA key.
     Rsa2048,
+    /// 3072-bit RSA key.
     Rsa3072,
+    /// 4096-bit RSA key.
     Rsa4096,
+    /// RSA key of a size aktualizr does not generate, in bits.
+    Rsa(u32),
+    /// ECDSA key on the NIST P-256 curve, `ecdsa-sha2-nistp256`.
+    EcdsaP256,
+    /// A key that is none of the above.
     Unknown,
 }
 
+impl KeyType {
+    /// Whether this is an RSA key of any size.
+    pub fn is_rsa(&self) -> bool {
+        matches!(
+            self,
+            KeyType::Rsa2048 | KeyType::Rsa3072 | KeyType::Rsa4096 | KeyType::Rsa(_)
+        )
+    }
+}
+
 impl FromStr for KeyType {
     type Err = String;
 
@@ -24,63 +49,413 @@ impl FromStr for KeyType {
             "rsa2048" => Ok(KeyType::Rsa2048),
             "rsa3072" => Ok(KeyType::Rsa3072),
             "rsa4096" => Ok(KeyType::Rsa4096),
-            _ => Ok(KeyType::Unknown),
+            "ecdsap256" | "ecdsa-sha2-nistp256" | "ecdsa_p256" => Ok(KeyType::EcdsaP256),
+            other => match other.strip_prefix("rsa").map(str::parse) {
+                Some(Ok(bits)) => Ok(KeyType::Rsa(bits)),
+                _ => Ok(KeyType::Unknown),
+            },
         }
     }
 }
 
 impl fmt::Display for KeyType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let key_type_str = match *self {
-            KeyType::Ed25519 => "Ed25519",
-            KeyType::Rsa2048 => "Rsa2048",
-            KeyType::Rsa3072 => "Rsa3072",
-            KeyType::Rsa4096 => "Rsa4096",
-            KeyType::Unknown => "Unknown",
-        };
-        write!(f, "{}", key_type_str)
+        match *self {
+            KeyType::Ed25519 => write!(f, "Ed25519"),
+            KeyType::Rsa2048 => write!(f, "Rsa2048"),
+            KeyType::Rsa3072 => write!(f, "Rsa3072"),
+            KeyType::Rsa4096 => write!(f, "Rsa4096"),
+            KeyType::Rsa(bits) => write!(f, "Rsa{}", bits),
+            KeyType::EcdsaP256 => write!(f, "EcdsaP256"),
+            KeyType::Unknown => write!(f, "Unknown"),
+        }
     }
 }
 
+/// Hashing, signing and signature verification with the key types aktualizr uses.
 pub struct Crypto;
 
 impl Crypto {
+    /// Accepts SubjectPublicKeyInfo ("PUBLIC KEY") and PKCS#1 ("RSA PUBLIC KEY") PEM
     pub fn identify_rsa_key_type(public_key: &str) -> Result<KeyType, Box<dyn Error>> {
-        let rsa = Rsa::public_key_from_pem(public_key.as_bytes())?;
+        let rsa = Rsa::public_key_from_pem(public_key.as_bytes())
+            .or_else(|_| Rsa::public_key_from_pem_pkcs1(public_key.as_bytes()))?;
         let key_length = rsa.size() * 8;
         match key_length {
             2048 => Ok(KeyType::Rsa2048),
             3072 => Ok(KeyType::Rsa3072),
             4096 => Ok(KeyType::Rsa4096),
+            bits => Ok(KeyType::Rsa(bits)),
+        }
+    }
+
+    /// Only the NIST P-256 curve is used by Uptane ECDSA keys
+    pub fn identify_ec_key_type(public_key: &str) -> Result<KeyType, Box<dyn Error>> {
+        let pkey = PKey::public_key_from_pem(public_key.as_bytes())?;
+        let curve = pkey.ec_key()?.group().curve_name();
+        match curve {
+            Some(Nid::X9_62_PRIME256V1) => Ok(KeyType::EcdsaP256),
             _ => Ok(KeyType::Unknown),
         }
     }
 
+    /// Guesses the type of a stored public key: PEM encoded RSA or ECDSA P-256, hex or PEM
+    /// encoded Ed25519
+    pub fn identify_key_type(public_key: &str) -> KeyType {
+        if let Ok(key_type) = Self::identify_rsa_key_type(public_key) {
+            return key_type;
+        }
+        if let Ok(key_type) = Self::identify_ec_key_type(public_key) {
+            return key_type;
+        }
+        match Self::ed25519_public_key_hex(public_key) {
+            Some(_) => KeyType::Ed25519,
+            None => KeyType::Unknown,
+        }
+    }
+
+    /// Ed25519 public keys are hex encoded in Uptane metadata, PEM ones are converted
+    pub fn ed25519_public_key_hex(public_key: &str) -> Option<String> {
+        let public_key = public_key.trim();
+        if public_key.starts_with("-----BEGIN") {
+            let pkey = PKey::public_key_from_pem(public_key.as_bytes()).ok()?;
+            if pkey.id() != Id::ED25519 {
+                return None;
+            }
+            return pkey.raw_public_key().ok().map(hex::encode);
+        }
+        match hex::decode(public_key) {
+            Ok(bytes) if bytes.len() == 32 => Some(public_key.to_lowercase()),
+            _ => None,
+        }
+    }
+
+    /// DER SubjectPublicKeyInfo of a public key, the same whichever encoding it is stored in:
+    /// SubjectPublicKeyInfo or PKCS#1 PEM, or hex encoded Ed25519
+    pub fn public_key_der(public_key: &str) -> Option<Vec<u8>> {
+        let pkey = PKey::public_key_from_pem(public_key.as_bytes())
+            .or_else(|_| {
+                Rsa::public_key_from_pem_pkcs1(public_key.as_bytes()).and_then(PKey::from_rsa)
+            })
+            .ok()
+            .or_else(|| {
+                let raw = hex::decode(Self::ed25519_public_key_hex(public_key)?).ok()?;
+                PKey::public_key_from_raw_bytes(&raw, Id::ED25519).ok()
+            })?;
+        pkey.public_key_to_der().ok()
+    }
+
+    /// Signs with RSASSA-PSS SHA-256 or Ed25519, as aktualizr does. RSA private keys are PEM,
+    /// Ed25519 ones PEM or hex encoded, either the 32 byte seed or libsodium's seed and
+    /// public key
+    pub fn sign(
+        key_type: &KeyType,
+        private_key: &str,
+        message: &str,
+    ) -> Result<Vec<u8>, Box<dyn Error>> {
+        match key_type {
+            key_type if key_type.is_rsa() => {
+                let pkey = PKey::private_key_from_pem(private_key.as_bytes())?;
+                let mut signer = Signer::new(openssl::hash::MessageDigest::sha256(), &pkey)?;
+                signer.set_rsa_padding(openssl::rsa::Padding::PKCS1_PSS)?;
+                signer.set_rsa_pss_saltlen(RsaPssSaltlen::DIGEST_LENGTH)?;
+                signer.update(message.as_bytes())?;
+                Ok(signer.sign_to_vec()?)
+            }
+            KeyType::EcdsaP256 => {
+                let pkey = PKey::private_key_from_pem(private_key.as_bytes())?;
+                let mut signer = Signer::new(openssl::hash::MessageDigest::sha256(), &pkey)?;
+                signer.update(message.as_bytes())?;
+                Ok(signer.sign_to_vec()?)
+            }
+            KeyType::Ed25519 => {
+                let pkey = Self::ed25519_private_key(private_key)?;
+                let mut signer = Signer::new_without_digest(&pkey)?;
+                Ok(signer.sign_oneshot_to_vec(message.as_bytes())?)
+            }
+            _ => Err(format!("signing with {} keys is not supported", key_type).into()),
+        }
+    }
+
+    fn ed25519_private_key(private_key: &str) -> Result<PKey<Private>, Box<dyn Error>> {
+        let private_key = private_key.trim();
+        let pkey = if private_key.starts_with("-----BEGIN") {
+            PKey::private_key_from_pem(private_key.as_bytes())?
+        } else {
+            let bytes = Zeroizing::new(hex::decode(private_key)?);
+            if bytes.len() != 32 && bytes.len() != 64 {
+                return Err(format!("invalid Ed25519 private key length {}", bytes.len()).into());
+            }
+            PKey::private_key_from_raw_bytes(&bytes[..32], Id::ED25519)?
+        };
+        if pkey.id() != Id::ED25519 {
+            return Err("not an Ed25519 private key".into());
+        }
+        Ok(pkey)
+    }
+
+    /// SHA-256 digest of the string.
     pub fn sha256digest(data: &str) -> Vec<u8> {
         digest(&SHA256, data.as_bytes()).as_ref().to_vec()
     }
 
+    /// Lowercase hex SHA-256 digest of the string, as used for key IDs.
     pub fn sha256digest_hex(data: &str) -> String {
         hex::encode(Self::sha256digest(data))
     }
 
-    pub fn rsa_pss_verify(public_key: &str, signature: &str, message: &str) -> bool {
-        let rsa = Rsa::public_key_from_pem(public_key.as_bytes()).unwrap();
-        let pkey = PKey::from_rsa(rsa).unwrap();
+    /// SHA-512 digest of the string.
+    pub fn sha512digest(data: &str) -> Vec<u8> {
+        digest(&SHA512, data.as_bytes()).as_ref().to_vec()
+    }
+
+    /// Lowercase hex SHA-512 digest of the string.
+    pub fn sha512digest_hex(data: &str) -> String {
+        hex::encode(Self::sha512digest(data))
+    }
+
+    /// Verifies the signature of the message with the public key, in the given scheme.
+    pub fn verify(
+        scheme: SignatureScheme,
+        public_key: &str,
+        signature: &[u8],
+        message: &[u8],
+    ) -> Result<(), VerificationError> {
+        match scheme {
+            SignatureScheme::RsassaPssSha256 => {
+                Self::rsa_pss_verify(public_key, signature, message)
+            }
+            SignatureScheme::Ed25519 => Self::ed25519_verify(public_key, signature, message),
+            SignatureScheme::EcdsaSha2Nistp256 => {
+                Self::ecdsa_p256_verify(public_key, signature, message)
+            }
+        }
+    }
+
+    /// OpenSSL recovers the salt length from the signature when verifying, so signatures
+    /// made with any salt length are accepted
+    pub fn rsa_pss_verify(
+        public_key: &str,
+        signature: &[u8],
+        message: &[u8],
+    ) -> Result<(), VerificationError> {
+        let rsa = Rsa::public_key_from_pem(public_key.as_bytes())
+            .or_else(|_| Rsa::public_key_from_pem_pkcs1(public_key.as_bytes()))
+            .map_err(|e| VerificationError::InvalidKey(format!("not an RSA public key: {}", e)))?;
+        let pkey = PKey::from_rsa(rsa).map_err(VerificationError::openssl)?;
         let mut verifier =
-            openssl::sign::Verifier::new(openssl::hash::MessageDigest::sha256(), &pkey).unwrap();
+            openssl::sign::Verifier::new(openssl::hash::MessageDigest::sha256(), &pkey)
+                .map_err(VerificationError::openssl)?;
         verifier
             .set_rsa_padding(openssl::rsa::Padding::PKCS1_PSS)
-            .unwrap();
-        verifier.update(message.as_bytes()).unwrap();
-        verifier.verify(signature.as_bytes()).is_ok()
+            .map_err(VerificationError::openssl)?;
+        verifier
+            .update(message)
+            .map_err(VerificationError::openssl)?;
+        match verifier.verify(signature) {
+            Ok(true) => Ok(()),
+            _ => Err(VerificationError::BadSignature),
+        }
     }
 
-    pub fn ed25519_verify(public_key: &str, signature: &str, message: &str) -> bool {
-        let public_key_bytes = hex::decode(public_key).unwrap();
+    /// Ed25519 public keys are hex encoded, PEM ones are accepted as well
+    pub fn ed25519_verify(
+        public_key: &str,
+        signature: &[u8],
+        message: &[u8],
+    ) -> Result<(), VerificationError> {
+        let public_key = Self::ed25519_public_key_hex(public_key).ok_or_else(|| {
+            VerificationError::InvalidKey("not an Ed25519 public key".to_string())
+        })?;
+        let public_key_bytes =
+            hex::decode(public_key).map_err(|e| VerificationError::InvalidKey(e.to_string()))?;
         let unparsed_key = UnparsedPublicKey::new(&ring::signature::ED25519, public_key_bytes);
         unparsed_key
-            .verify(message.as_bytes(), signature.as_bytes())
-            .is_ok()
+            .verify(message, signature)
+            .map_err(|_| VerificationError::BadSignature)
+    }
+
+    /// Signatures are DER encoded as produced by OpenSSL and HSMs, or the raw 64 byte r || s
+    /// concatenation used by some signing services
+    pub fn ecdsa_p256_verify(
+        public_key: &str,
+        signature: &[u8],
+        message: &[u8],
+    ) -> Result<(), VerificationError> {
+        if Self::identify_ec_key_type(public_key).ok() != Some(KeyType::EcdsaP256) {
+            return Err(VerificationError::InvalidKey(
+                "not an ECDSA P-256 public key".to_string(),
+            ));
+        }
+        let pkey = PKey::public_key_from_pem(public_key.as_bytes())
+            .map_err(|e| VerificationError::InvalidKey(e.to_string()))?;
+        // DER encoded as openssl writes them, or the raw r || s some signers produce. A DER
+        // signature can be 64 bytes long as well, so that form is tried first.
+        let signature = if EcdsaSig::from_der(signature).is_ok() {
+            signature.to_vec()
+        } else if signature.len() == 64 {
+            let r = BigNum::from_slice(&signature[..32]).map_err(VerificationError::openssl)?;
+            let s = BigNum::from_slice(&signature[32..]).map_err(VerificationError::openssl)?;
+            EcdsaSig::from_private_components(r, s)
+                .and_then(|signature| signature.to_der())
+                .map_err(VerificationError::openssl)?
+        } else {
+            return Err(VerificationError::BadSignature);
+        };
+        let mut verifier =
+            openssl::sign::Verifier::new(openssl::hash::MessageDigest::sha256(), &pkey)
+                .map_err(VerificationError::openssl)?;
+        verifier
+            .update(message)
+            .map_err(VerificationError::openssl)?;
+        match verifier.verify(&signature) {
+            Ok(true) => Ok(()),
+            _ => Err(VerificationError::BadSignature),
+        }
+    }
+}
+
+/// Signature schemes by their names in the method field of TUF and Uptane signatures
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SignatureScheme {
+    /// `rsassa-pss-sha256`, written `rsassa-pss` by aktualizr.
+    RsassaPssSha256,
+    /// `ed25519`.
+    Ed25519,
+    /// `ecdsa-sha2-nistp256`.
+    EcdsaSha2Nistp256,
+}
+
+impl SignatureScheme {
+    /// Scheme a key of the given type signs with, used when a signature does not name one
+    pub fn for_key_type(key_type: &KeyType) -> Option<Self> {
+        match key_type {
+            KeyType::Ed25519 => Some(SignatureScheme::Ed25519),
+            KeyType::EcdsaP256 => Some(SignatureScheme::EcdsaSha2Nistp256),
+            key_type if key_type.is_rsa() => Some(SignatureScheme::RsassaPssSha256),
+            _ => None,
+        }
+    }
+
+    /// Whether keys of the given type sign with this scheme.
+    pub fn supports(&self, key_type: &KeyType) -> bool {
+        Self::for_key_type(key_type) == Some(*self)
     }
 }
+
+impl FromStr for SignatureScheme {
+    type Err = VerificationError;
+
+    // aktualizr writes "rsassa-pss" for what TUF calls "rsassa-pss-sha256"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "rsassa-pss-sha256" | "rsassa-pss" => Ok(SignatureScheme::RsassaPssSha256),
+            "ed25519" => Ok(SignatureScheme::Ed25519),
+            "ecdsa-sha2-nistp256" => Ok(SignatureScheme::EcdsaSha2Nistp256),
+            _ => Err(VerificationError::UnsupportedScheme(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for SignatureScheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignatureScheme::RsassaPssSha256 => write!(f, "rsassa-pss-sha256"),
+            SignatureScheme::Ed25519 => write!(f, "ed25519"),
+            SignatureScheme::EcdsaSha2Nistp256 => write!(f, "ecdsa-sha2-nistp256"),
+        }
+    }
+}
+
+/// aktualizr base64 encodes signatures, the TUF reference implementation hex encodes them
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SignatureEncoding {
+    /// Base64, as aktualizr writes them.
+    Base64,
+    /// Hex, as the TUF reference implementation writes them.
+    Hex,
+}
+
+impl SignatureEncoding {
+    /// Hex only uses characters base64 also uses, a signature that is valid hex is taken as
+    /// such. Base64 signatures of real keys are padded or long enough to never be valid hex.
+    pub fn detect(signature: &str) -> Self {
+        let signature = signature.trim();
+        if !signature.is_empty()
+            && signature.len().is_multiple_of(2)
+            && signature.bytes().all(|b| b.is_ascii_hexdigit())
+        {
+            SignatureEncoding::Hex
+        } else {
+            SignatureEncoding::Base64
+        }
+    }
+
+    /// Raw signature bytes.
+    pub fn decode(&self, signature: &str) -> Result<Vec<u8>, VerificationError> {
+        let signature = signature.trim();
+        match self {
+            SignatureEncoding::Base64 => openssl::base64::decode_block(signature)
+                .map_err(|e| VerificationError::InvalidSignature(format!("invalid base64: {}", e))),
+            SignatureEncoding::Hex => hex::decode(signature)
+                .map_err(|e| VerificationError::InvalidSignature(format!("invalid hex: {}", e))),
+        }
+    }
+}
+
+impl fmt::Display for SignatureEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignatureEncoding::Base64 => write!(f, "base64"),
+            SignatureEncoding::Hex => write!(f, "hex"),
+        }
+    }
+}
+
+/// Why a signature could not be verified. Only BadSignature means the signature was checked
+/// and does not match, the others mean it could not be checked at all.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum VerificationError {
+    /// The public key cannot be read.
+    InvalidKey(String),
+    /// The signature cannot be decoded.
+    InvalidSignature(String),
+    /// The signed message cannot be serialized.
+    InvalidMessage(String),
+    /// The signature names a scheme this reader does not know.
+    UnsupportedScheme(String),
+    /// The signature scheme does not fit the type of the key.
+    SchemeMismatch {
+        /// Scheme named by the signature.
+        scheme: SignatureScheme,
+        /// Type of the key the signature was checked against.
+        key_type: KeyType,
+    },
+    /// The signature was checked and does not match.
+    BadSignature,
+}
+
+impl VerificationError {
+    fn openssl(error: openssl::error::ErrorStack) -> Self {
+        VerificationError::InvalidSignature(error.to_string())
+    }
+}
+
+impl fmt::Display for VerificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerificationError::InvalidKey(e) => write!(f, "invalid public key: {}", e),
+            VerificationError::InvalidSignature(e) => write!(f, "invalid signature: {}", e),
+            VerificationError::InvalidMessage(e) => write!(f, "invalid signed message: {}", e),
+            VerificationError::UnsupportedScheme(scheme) => {
+                write!(f, "unsupported signature scheme '{}'", scheme)
+            }
+            VerificationError::SchemeMismatch { scheme, key_type } => {
+                write!(f, "{} signature made with a {} key", scheme, key_type)
+            }
+            VerificationError::BadSignature => write!(f, "signature does not match"),
+        }
+    }
+}
+
+impl Error for VerificationError {}
@@ -5,10 +5,15 @@ use std::fmt;
 const ECU_SERIAL_MIN_LENGTH: usize = 1;
 const ECU_SERIAL_MAX_LENGTH: usize = 64;
 
+/// Serial of an ECU, unique within a device.
+///
+/// aktualizr identifies ECUs by serial in its tables and in the Director metadata. Serials are
+/// between 1 and 64 characters long; values read from storage are validated the same way.
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct EcuSerial(String);
 
 impl EcuSerial {
+    /// Validates the length of `serial`.
     pub fn new(serial: &str) -> Result<Self, EcuSerialError> {
         if serial.len() < ECU_SERIAL_MIN_LENGTH {
             return Err(EcuSerialError(format!(
@@ -24,6 +29,8 @@ impl EcuSerial {
         }
         Ok(EcuSerial(serial.to_string()))
     }
+
+    /// Placeholder for ECUs whose serial is not known.
     pub fn unknown() -> Self {
         EcuSerial("Unknown".to_string())
     }
@@ -59,6 +66,7 @@ impl FromSql for EcuSerial {
     }
 }
 
+/// An ECU serial of invalid length.
 #[derive(Debug)]
 pub struct EcuSerialError(String);
 
@@ -0,0 +1,205 @@
+use crate::config::ConfigError;
+use crate::tuf_metadata::MetadataError;
+use std::fmt;
+use std::path::PathBuf;
+
+/// Result of the storage reader, failing with an [`Error`] by default.
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Everything that can go wrong while reading a storage.
+///
+/// Every variant maps to its own process exit code, see [`Error::exit_code`]:
+///
+/// | Code | Variant                | Meaning                                              |
+/// |------|------------------------|------------------------------------------------------|
+/// | 0    |                        | Success                                              |
+/// | 1    | `Storage`              | The database exists but cannot be read, e.g. corrupt |
+/// | 2    | `Config`               | Invalid command line or configuration                |
+/// | 3    | `StorageMissing`       | No database at the configured path                   |
+/// | 4    | `PermissionDenied`     | The database cannot be opened by this user           |
+/// | 5    | `SchemaTooOld`         | The schema lacks tables or columns the reader needs  |
+/// | 6    |                        | No longer used                                       |
+/// | 7    | `NotProvisioned`       | The requested data is not in storage yet             |
+/// | 8    | `Parse`                | Stored data is malformed                             |
+/// | 9    | `Verification`         | Metadata verification failed                         |
+/// | 10   | `MetadataExpiringSoon` | A role or certificate expires within the window      |
+/// | 11   | `MetadataExpired`      | A role or certificate already expired                |
+/// | 12   | `Timeout`              | The device was not provisioned in time               |
+/// | 13   | `Migration`            | The storage could not be migrated                    |
+/// | 14   | `Io`                   | A file could not be read or written                  |
+/// | 15   | `SecretsRefused`       | Private keys were held back from the output          |
+/// | 16   | `SchemaUnknown`        | The database has no version table or an invalid one  |
+///
+/// When a run finds several problems, the exit code is the one of the most severe, see
+/// [`Error::severity`].
+#[derive(Debug)]
+pub enum Error {
+    /// The database cannot be read (exit code 1).
+    Storage(rusqlite::Error),
+    /// Invalid command line or configuration (exit code 2).
+    Config(ConfigError),
+    /// No database at the path (exit code 3).
+    StorageMissing(PathBuf),
+    /// The database at the path cannot be opened by this user (exit code 4).
+    PermissionDenied(PathBuf),
+    /// The schema lacks tables or columns the reader needs (exit code 5).
+    SchemaTooOld {
+        /// Recorded schema version, if any.
+        version: Option<i32>,
+        /// Features the schema does not support.
+        unavailable: Vec<String>,
+    },
+    /// The requested data is not in storage yet (exit code 7).
+    NotProvisioned(String),
+    /// Stored data is malformed (exit code 8).
+    Parse(String),
+    /// Metadata or credentials failed verification (exit code 9).
+    Verification(String),
+    /// A role or certificate expires within the window (exit code 10).
+    MetadataExpiringSoon(String),
+    /// A role or certificate already expired (exit code 11).
+    MetadataExpired(String),
+    /// The device was not provisioned in time (exit code 12).
+    Timeout(String),
+    /// The storage could not be migrated (exit code 13).
+    Migration(String),
+    /// A file could not be read or written (exit code 14).
+    Io {
+        /// File that could not be read or written.
+        path: PathBuf,
+        /// Why it could not be.
+        error: std::io::Error,
+    },
+    /// Private keys were held back from the output (exit code 15).
+    SecretsRefused(String),
+    /// The database has no version table or an invalid one (exit code 16).
+    SchemaUnknown(String),
+}
+
+impl Error {
+    /// Process exit code reported for the error.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Storage(_) => 1,
+            Error::Config(_) => 2,
+            Error::StorageMissing(_) => 3,
+            Error::PermissionDenied(_) => 4,
+            Error::SchemaTooOld { .. } => 5,
+            Error::NotProvisioned(_) => 7,
+            Error::Parse(_) => 8,
+            Error::Verification(_) => 9,
+            Error::MetadataExpiringSoon(_) => 10,
+            Error::MetadataExpired(_) => 11,
+            Error::Timeout(_) => 12,
+            Error::Migration(_) => 13,
+            Error::Io { .. } => 14,
+            Error::SecretsRefused(_) => 15,
+            Error::SchemaUnknown(_) => 16,
+        }
+    }
+
+    /// Rank of the error when several are found in one run, the highest one decides the exit
+    /// code. From the most to the least severe:
+    ///
+    /// 1. errors that stop the run: 1 to 5, 12 to 14 and 16
+    /// 2. `Verification` (9): the metadata or credentials cannot be trusted
+    /// 3. `MetadataExpired` (11)
+    /// 4. `MetadataExpiringSoon` (10)
+    /// 5. `Parse` (8)
+    /// 6. `NotProvisioned` (7): some of the requested data is not there yet
+    /// 7. `SecretsRefused` (15): only a part of the output was held back
+    pub fn severity(&self) -> u8 {
+        match self {
+            Error::Storage(_)
+            | Error::Config(_)
+            | Error::StorageMissing(_)
+            | Error::PermissionDenied(_)
+            | Error::SchemaTooOld { .. }
+            | Error::Timeout(_)
+            | Error::Migration(_)
+            | Error::Io { .. }
+            | Error::SchemaUnknown(_) => 7,
+            Error::Verification(_) => 6,
+            Error::MetadataExpired(_) => 5,
+            Error::MetadataExpiringSoon(_) => 4,
+            Error::Parse(_) => 3,
+            Error::NotProvisioned(_) => 2,
+            Error::SecretsRefused(_) => 1,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Storage(e) => write!(f, "Storage Error: {}", e),
+            Error::Config(e) => write!(f, "{}", e),
+            Error::StorageMissing(path) => {
+                write!(f, "Storage Error: {} does not exist", path.display())
+            }
+            Error::PermissionDenied(path) => {
+                write!(f, "Storage Error: permission denied on {}", path.display())
+            }
+            Error::SchemaTooOld {
+                version: Some(version),
+                unavailable,
+            } => write!(
+                f,
+                "Schema Error: storage at schema version {} cannot be read, it does not support {}",
+                version,
+                unavailable.join(", ")
+            ),
+            Error::SchemaTooOld {
+                version: None,
+                unavailable,
+            } => write!(
+                f,
+                "Schema Error: storage without a schema version cannot be read, it does not support {}",
+                unavailable.join(", ")
+            ),
+            Error::NotProvisioned(what) => write!(f, "Not Provisioned: {}", what),
+            Error::Parse(e) => write!(f, "Parse Error: {}", e),
+            Error::Verification(e) => write!(f, "Verification Error: {}", e),
+            Error::MetadataExpiringSoon(e) => write!(f, "Expiry Warning: {}", e),
+            Error::MetadataExpired(e) => write!(f, "Expiry Error: {}", e),
+            Error::Timeout(e) => write!(f, "Timeout: {}", e),
+            Error::Migration(e) => write!(f, "Migration Error: {}", e),
+            Error::Io { path, error } => write!(f, "IO Error: {}: {}", path.display(), error),
+            Error::SecretsRefused(reason) => write!(
+                f,
+                "Secrets Refused: private keys not printed, {}. Use --show-secrets to print them anyway",
+                reason
+            ),
+            Error::SchemaUnknown(e) => write!(f, "Schema Error: unknown schema, {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Storage(e) => Some(e),
+            Error::Config(e) => Some(e),
+            Error::Io { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<rusqlite::Error> for Error {
+    fn from(e: rusqlite::Error) -> Self {
+        Error::Storage(e)
+    }
+}
+
+impl From<ConfigError> for Error {
+    fn from(e: ConfigError) -> Self {
+        Error::Config(e)
+    }
+}
+
+impl From<MetadataError> for Error {
+    fn from(e: MetadataError) -> Self {
+        Error::Parse(e.to_string())
+    }
+}
@@ -0,0 +1,549 @@
+use crate::config::Config;
+use crate::ecu_serial::EcuSerial;
+use crate::error::{Error, Result};
+use crate::hardware_identifier::HardwareIdentifier;
+use crate::public_key::PublicKey;
+use crate::schema;
+use crate::sqlstorage::SQLStorage;
+use crate::tuf_metadata::{Metadata, Root, SignedRole, Snapshot, Targets, Timestamp};
+use crate::tuf_repository_type::RepositoryType;
+use crate::tuf_roles::Role;
+use crate::tuf_version::Version;
+use log::{debug, info};
+use rusqlite::{params, Connection};
+use serde_json::{json, Value};
+use std::fmt;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+// Files aktualizr kept in its storage directory before it moved to SQLite, shared with the
+// export
+pub(crate) const DEVICE_ID_FILE: &str = "device_id";
+pub(crate) const IS_REGISTERED_FILE: &str = "is_registered";
+pub(crate) const PRIMARY_SERIAL_FILE: &str = "primary_ecu_serial";
+pub(crate) const PRIMARY_HARDWARE_ID_FILE: &str = "primary_ecu_hardware_id";
+pub(crate) const SECONDARIES_FILE: &str = "secondaries_list";
+
+// Subdirectories of the metadata directory, one per repo, shared with the export
+pub(crate) const IMAGE_METADATA_DIR: &str = "repo";
+pub(crate) const DIRECTOR_METADATA_DIR: &str = "director";
+
+/// Where the legacy storage keeps each file. The key, certificate and metadata paths are the
+/// storage options of the configuration, relative to the storage directory.
+#[derive(Debug, Clone)]
+pub struct FsLayout {
+    /// Storage directory holding the device ID, registration and ECU files.
+    pub directory: PathBuf,
+    /// Directory of the metadata, with one subdirectory per repo.
+    pub metadata: PathBuf,
+    /// Uptane private key of the Primary.
+    pub private_key: PathBuf,
+    /// Uptane public key of the Primary.
+    pub public_key: PathBuf,
+    /// Root CA certificate.
+    pub ca_cert: PathBuf,
+    /// TLS client private key.
+    pub client_pkey: PathBuf,
+    /// TLS client certificate.
+    pub client_cert: PathBuf,
+}
+
+impl FsLayout {
+    /// Layout of the storage in the directory under the storage options of the configuration.
+    pub fn from_config(directory: &Path, config: &Config) -> Self {
+        let path = |key: &str| directory.join(config.get(key).unwrap_or_default());
+        FsLayout {
+            directory: directory.to_path_buf(),
+            metadata: path("storage.uptane_metadata_path"),
+            private_key: path("storage.uptane_private_key_path"),
+            public_key: path("storage.uptane_public_key_path"),
+            ca_cert: path("storage.tls_cacert_path"),
+            client_pkey: path("storage.tls_pkey_path"),
+            client_cert: path("storage.tls_clientcert_path"),
+        }
+    }
+}
+
+// Metadata file found in the legacy storage, with the version declared in its content
+#[derive(Debug, Clone)]
+struct MetadataFile {
+    repo: RepositoryType,
+    role: Role,
+    version: i32,
+    raw: String,
+}
+
+/// What was imported, to report back to the user
+#[derive(Debug, Clone, Default)]
+pub struct ImportSummary {
+    /// Database the storage was imported into.
+    pub database_path: PathBuf,
+    /// Imported device ID.
+    pub device_id: Option<String>,
+    /// Whether the device was registered.
+    pub registered: bool,
+    /// Serial and hardware ID of every imported ECU, the Primary first.
+    pub ecus: Vec<(String, String)>,
+    /// Whether the Primary's Uptane keys were imported.
+    pub primary_keys: bool,
+    /// Whether TLS credentials were imported.
+    pub tls_credentials: bool,
+    /// Repo, role and version of every imported metadata file.
+    pub metadata: Vec<(String, String, i32)>,
+}
+
+impl ImportSummary {
+    /// Same content as the Display output.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "database": self.database_path.display().to_string(),
+            "device_id": self.device_id,
+            "registered": self.registered,
+            "ecus": self.ecus.iter().map(|(serial, hardware_id)| json!({
+                "serial": serial,
+                "hardware_id": hardware_id,
+            })).collect::<Vec<_>>(),
+            "primary_keys": self.primary_keys,
+            "tls_credentials": self.tls_credentials,
+            "metadata": self.metadata.iter().map(|(repo, role, version)| json!({
+                "repo": repo,
+                "role": role,
+                "version": version,
+            })).collect::<Vec<_>>(),
+        })
+    }
+}
+
+impl fmt::Display for ImportSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Imported into {}", self.database_path.display())?;
+        writeln!(
+            f,
+            "   Device ID: {}",
+            self.device_id.as_deref().unwrap_or("none")
+        )?;
+        writeln!(f, "   Registered: {}", self.registered)?;
+        for (serial, hardware_id) in &self.ecus {
+            writeln!(f, "   ECU: {} ({})", serial, hardware_id)?;
+        }
+        writeln!(f, "   Primary keys: {}", self.primary_keys)?;
+        writeln!(f, "   TLS credentials: {}", self.tls_credentials)?;
+        for (repo, role, version) in &self.metadata {
+            writeln!(f, "   {} {} v{}", repo, role, version)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads a storage written by aktualizr's filesystem backend and writes its content to a new
+/// SQLite storage. Everything is validated before the database is created, so a malformed
+/// file aborts the import without leaving a partial storage behind.
+pub struct FsImporter {
+    layout: FsLayout,
+}
+
+impl FsImporter {
+    /// Importer of the storage in the layout.
+    pub fn new(layout: FsLayout) -> Self {
+        FsImporter { layout }
+    }
+
+    /// Creates the database at the path and imports the storage into it. The database must not
+    /// exist yet.
+    pub fn import(&self, database_path: &Path) -> Result<ImportSummary> {
+        if !self.layout.directory.is_dir() {
+            return Err(Error::StorageMissing(self.layout.directory.clone()));
+        }
+        if database_path.exists() {
+            return Err(Error::Io {
+                path: database_path.to_path_buf(),
+                error: ErrorKind::AlreadyExists.into(),
+            });
+        }
+
+        let device_id = self.read_optional(&self.layout.directory.join(DEVICE_ID_FILE))?;
+        let device_id = device_id.map(|id| id.trim().to_string());
+        let registered = self.layout.directory.join(IS_REGISTERED_FILE).exists();
+        let ecus = self.read_ecus()?;
+        let primary_keys = self.read_primary_keys()?;
+        let tls_credentials = self.read_tls_credentials()?;
+        let metadata = self.read_metadata()?;
+
+        let summary = ImportSummary {
+            database_path: database_path.to_path_buf(),
+            device_id,
+            registered,
+            ecus: ecus
+                .iter()
+                .map(|(serial, hardware_id)| {
+                    (serial.to_string(), hardware_id.to_string().to_owned())
+                })
+                .collect(),
+            primary_keys: primary_keys.is_some(),
+            tls_credentials: tls_credentials.is_some(),
+            metadata: metadata
+                .iter()
+                .map(|m| (m.repo.to_string(), m.role.to_string(), m.version))
+                .collect(),
+        };
+
+        // Written next to the destination first, so that an interrupted import does not
+        // look like a storage
+        let partial = PathBuf::from(format!("{}.import", database_path.display()));
+        if partial.exists() {
+            fs::remove_file(&partial).map_err(|error| Error::Io {
+                path: partial.clone(),
+                error,
+            })?;
+        }
+        let written = Self::write(
+            &partial,
+            &summary,
+            &ecus,
+            primary_keys.as_ref(),
+            tls_credentials.as_ref(),
+            &metadata,
+        );
+        if let Err(e) = written {
+            let _ = fs::remove_file(&partial);
+            return Err(e);
+        }
+        fs::rename(&partial, database_path).map_err(|error| Error::Io {
+            path: database_path.to_path_buf(),
+            error,
+        })?;
+
+        // The result has to be readable like any aktualizr storage
+        SQLStorage::new(database_path, false)?;
+        info!(
+            "Imported {} into {}",
+            self.layout.directory.display(),
+            database_path.display()
+        );
+        Ok(summary)
+    }
+
+    fn write(
+        database_path: &Path,
+        summary: &ImportSummary,
+        ecus: &[(EcuSerial, HardwareIdentifier)],
+        primary_keys: Option<&(Option<String>, String)>,
+        tls_credentials: Option<&[Option<Vec<u8>>; 3]>,
+        metadata: &[MetadataFile],
+    ) -> Result<()> {
+        let mut conn = Connection::open(database_path)?;
+        let tx = conn.transaction()?;
+        schema::create_unversioned(&tx)?;
+
+        tx.execute(
+            "INSERT INTO device_info(unique_mark, device_id, is_registered) VALUES (0, ?, ?);",
+            params![summary.device_id, summary.registered],
+        )?;
+
+        // The Primary comes first in the legacy storage
+        for (index, (serial, hardware_id)) in ecus.iter().enumerate() {
+            tx.execute(
+                "INSERT INTO ecus(id, serial, hardware_id, is_primary) VALUES (?, ?, ?, ?);",
+                params![
+                    index as i64,
+                    serial.to_string(),
+                    hardware_id.to_string(),
+                    index == 0
+                ],
+            )?;
+        }
+
+        if let Some((private, public)) = primary_keys {
+            tx.execute(
+                "INSERT INTO primary_keys(unique_mark, private, public) VALUES (0, ?, ?);",
+                params![private, public],
+            )?;
+        }
+
+        if let Some([ca_cert, client_cert, client_pkey]) = tls_credentials {
+            tx.execute(
+                "INSERT INTO tls_creds(ca_cert, ca_cert_format, client_cert, client_cert_format, client_pkey, client_pkey_format) VALUES (?, 'pem', ?, 'pem', ?, 'pem');",
+                params![ca_cert, client_cert, client_pkey],
+            )?;
+        }
+
+        for file in metadata {
+            tx.execute(
+                "INSERT INTO meta(meta, repo, meta_type, version) VALUES (?, ?, ?, ?);",
+                params![
+                    file.raw.as_bytes(),
+                    i32::from(file.repo),
+                    file.role.to_int(),
+                    file.version
+                ],
+            )?;
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    // The Primary from its serial and hardware ID files, the Secondaries from the list
+    // holding one "<serial> <hardware ID>" line each
+    fn read_ecus(&self) -> Result<Vec<(EcuSerial, HardwareIdentifier)>> {
+        let serial_path = self.layout.directory.join(PRIMARY_SERIAL_FILE);
+        let hardware_id_path = self.layout.directory.join(PRIMARY_HARDWARE_ID_FILE);
+        let (serial, hardware_id) = match (
+            self.read_optional(&serial_path)?,
+            self.read_optional(&hardware_id_path)?,
+        ) {
+            (Some(serial), Some(hardware_id)) => (serial, hardware_id),
+            (None, None) => {
+                debug!("No Primary ECU in {}", self.layout.directory.display());
+                return Ok(Vec::new());
+            }
+            (Some(_), None) => return Err(Self::missing_pair(&hardware_id_path, &serial_path)),
+            (None, Some(_)) => return Err(Self::missing_pair(&serial_path, &hardware_id_path)),
+        };
+
+        let mut ecus = vec![Self::parse_ecu(
+            &serial_path,
+            serial.trim(),
+            hardware_id.trim(),
+        )?];
+
+        let secondaries_path = self.layout.directory.join(SECONDARIES_FILE);
+        if let Some(secondaries) = self.read_optional(&secondaries_path)? {
+            for (number, line) in secondaries.lines().enumerate() {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let mut fields = line.split_whitespace();
+                let (Some(serial), Some(hardware_id), None) =
+                    (fields.next(), fields.next(), fields.next())
+                else {
+                    return Err(Error::Parse(format!(
+                        "{}:{}: expected a serial and a hardware ID",
+                        secondaries_path.display(),
+                        number + 1
+                    )));
+                };
+                ecus.push(Self::parse_ecu(&secondaries_path, serial, hardware_id)?);
+            }
+        }
+        Ok(ecus)
+    }
+
+    fn parse_ecu(
+        path: &Path,
+        serial: &str,
+        hardware_id: &str,
+    ) -> Result<(EcuSerial, HardwareIdentifier)> {
+        let serial = EcuSerial::new(serial)
+            .map_err(|e| Error::Parse(format!("{}: {}", path.display(), e)))?;
+        let hardware_id = HardwareIdentifier::new(hardware_id)
+            .map_err(|e| Error::Parse(format!("{}: {}", path.display(), e)))?;
+        Ok((serial, hardware_id))
+    }
+
+    // The public key has to be one the reader can use, the private key is copied as it is.
+    // Exports without secrets have the public key only.
+    fn read_primary_keys(&self) -> Result<Option<(Option<String>, String)>> {
+        let private = self.read_optional(&self.layout.private_key)?;
+        if !self.layout.public_key.exists() {
+            if private.is_some() {
+                return Err(Self::missing_pair(
+                    &self.layout.public_key,
+                    &self.layout.private_key,
+                ));
+            }
+            return Ok(None);
+        }
+
+        let public = PublicKey::from_path(&self.layout.public_key)
+            .map_err(|e| Error::Parse(format!("{}: {}", self.layout.public_key.display(), e)))?;
+        if private.is_none() {
+            info!(
+                "No private key at {}, importing the public key only",
+                self.layout.private_key.display()
+            );
+        }
+        Ok(Some((private, public.value().to_string())))
+    }
+
+    fn read_tls_credentials(&self) -> Result<Option<[Option<Vec<u8>>; 3]>> {
+        let mut credentials = [None, None, None];
+        for (credential, path) in credentials.iter_mut().zip([
+            &self.layout.ca_cert,
+            &self.layout.client_cert,
+            &self.layout.client_pkey,
+        ]) {
+            *credential = match fs::read(path) {
+                Ok(content) => Some(content),
+                Err(e) if e.kind() == ErrorKind::NotFound => None,
+                Err(error) => {
+                    return Err(Error::Io {
+                        path: path.clone(),
+                        error,
+                    })
+                }
+            };
+        }
+
+        if credentials.iter().all(Option::is_none) {
+            return Ok(None);
+        }
+        Ok(Some(credentials))
+    }
+
+    // Every repo directory has the latest version of each role as <role>.json, and older
+    // root versions as <role>_v<version>.json. Versioned files of the other roles, as the
+    // export writes them, are read as well.
+    fn read_metadata(&self) -> Result<Vec<MetadataFile>> {
+        let mut metadata = Vec::new();
+        for (directory, repo) in [
+            (IMAGE_METADATA_DIR, RepositoryType::image()),
+            (DIRECTOR_METADATA_DIR, RepositoryType::director()),
+        ] {
+            let directory = self.layout.metadata.join(directory);
+            if !directory.is_dir() {
+                debug!("No {} metadata in {}", repo, directory.display());
+                continue;
+            }
+
+            for version in 1.. {
+                let path = directory.join(Version::from_int(version).role_file_name(Role::ROOT));
+                let Some(raw) = self.read_optional(&path)? else {
+                    break;
+                };
+                metadata.push(Self::parse_versioned_metadata(
+                    &path,
+                    repo,
+                    Role::root(),
+                    version,
+                    raw,
+                )?);
+            }
+
+            for role in [Role::timestamp(), Role::snapshot(), Role::targets()] {
+                for (version, path) in Self::versioned_files(&directory, &role.to_string())? {
+                    let raw = fs::read_to_string(&path).map_err(|error| Error::Io {
+                        path: path.clone(),
+                        error,
+                    })?;
+                    metadata.push(Self::parse_versioned_metadata(
+                        &path,
+                        repo,
+                        role.clone(),
+                        version,
+                        raw,
+                    )?);
+                }
+            }
+
+            for role in [
+                Role::root(),
+                Role::timestamp(),
+                Role::snapshot(),
+                Role::targets(),
+            ] {
+                let path = directory.join(format!("{}.json", role));
+                let Some(raw) = self.read_optional(&path)? else {
+                    continue;
+                };
+                let file = Self::parse_metadata(&path, repo, role, raw)?;
+                let duplicate = metadata.iter().any(|m| {
+                    m.repo == file.repo && m.role == file.role && m.version == file.version
+                });
+                if !duplicate {
+                    metadata.push(file);
+                }
+            }
+        }
+        Ok(metadata)
+    }
+
+    // The <role>_v<version>.json files of a role in a repo directory, by version
+    fn versioned_files(directory: &Path, role: &str) -> Result<Vec<(i32, PathBuf)>> {
+        let entries = fs::read_dir(directory).map_err(|error| Error::Io {
+            path: directory.to_path_buf(),
+            error,
+        })?;
+        let prefix = format!("{}_v", role);
+        let mut files = Vec::new();
+        for entry in entries {
+            let entry = entry.map_err(|error| Error::Io {
+                path: directory.to_path_buf(),
+                error,
+            })?;
+            let name = entry.file_name();
+            let version = name
+                .to_str()
+                .and_then(|name| name.strip_prefix(&prefix))
+                .and_then(|name| name.strip_suffix(".json"))
+                .and_then(|version| version.parse::<i32>().ok());
+            if let Some(version) = version {
+                files.push((version, entry.path()));
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    // A <role>_v<version>.json file has to hold that version
+    fn parse_versioned_metadata(
+        path: &Path,
+        repo: RepositoryType,
+        role: Role,
+        version: i32,
+        raw: String,
+    ) -> Result<MetadataFile> {
+        let file = Self::parse_metadata(path, repo, role.clone(), raw)?;
+        if file.version != version {
+            return Err(Error::Parse(format!(
+                "{}: holds {} version {}",
+                path.display(),
+                role,
+                file.version
+            )));
+        }
+        Ok(file)
+    }
+
+    fn parse_metadata(
+        path: &Path,
+        repo: RepositoryType,
+        role: Role,
+        raw: String,
+    ) -> Result<MetadataFile> {
+        let version = match role.to_string().as_str() {
+            Role::ROOT => Metadata::<Root>::parse(&raw).map(|m| m.signed.version()),
+            Role::TIMESTAMP => Metadata::<Timestamp>::parse(&raw).map(|m| m.signed.version()),
+            Role::SNAPSHOT => Metadata::<Snapshot>::parse(&raw).map(|m| m.signed.version()),
+            _ => Metadata::<Targets>::parse(&raw).map(|m| m.signed.version()),
+        }
+        .map_err(|e| Error::Parse(format!("{}: {}", path.display(), e)))?;
+
+        Ok(MetadataFile {
+            repo,
+            role,
+            version,
+            raw,
+        })
+    }
+
+    fn read_optional(&self, path: &Path) -> Result<Option<String>> {
+        match fs::read_to_string(path) {
+            Ok(content) => Ok(Some(content)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(Error::Io {
+                path: path.to_path_buf(),
+                error,
+            }),
+        }
+    }
+
+    fn missing_pair(missing: &Path, present: &Path) -> Error {
+        Error::Parse(format!(
+            "{} is missing while {} exists",
+            missing.display(),
+            present.display()
+        ))
+    }
+}
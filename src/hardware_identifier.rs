@@ -4,10 +4,15 @@ use std::fmt;
 
 const HWID_MAX_LENGTH: usize = 200;
 
+/// Hardware ID of an ECU, naming the kind of hardware it is.
+///
+/// The Director assigns targets per hardware ID. IDs are at most 200 characters long; values
+/// read from storage are validated the same way.
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct HardwareIdentifier(String);
 
 impl HardwareIdentifier {
+    /// Validates the length of `hwid`.
     pub fn new(hwid: &str) -> Result<Self, HardwareIdentifierError> {
         if hwid.len() > HWID_MAX_LENGTH {
             return Err(HardwareIdentifierError(format!(
@@ -18,10 +23,12 @@ impl HardwareIdentifier {
         Ok(HardwareIdentifier(hwid.to_string()))
     }
 
+    /// Placeholder for ECUs whose hardware ID is not known.
     pub fn unknown() -> Self {
         HardwareIdentifier("Unknown".to_string())
     }
 
+    /// The hardware ID as a string slice.
     pub fn to_string(&self) -> &str {
         &self.0
     }
@@ -57,6 +64,7 @@ impl PartialOrd for HardwareIdentifier {
     }
 }
 
+/// A hardware ID that is too long.
 #[derive(Debug)]
 pub struct HardwareIdentifierError(String);
 
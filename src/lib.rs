@@ -0,0 +1,83 @@
+//! Reader for the SQLite storage of an aktualizr device.
+//!
+//! The crate exposes what the `oxidizr` command line tool shows: device information,
+//! ECUs and Secondaries, Uptane and TLS keys and the Image and Director repo metadata,
+//! together with the checks built on top of it.
+//!
+//! ```no_run
+//! use oxidizr::config::Config;
+//! use oxidizr::sqlstorage::SQLStorage;
+//!
+//! let config = Config::load_default()?;
+//! let storage = SQLStorage::new(&config.sqldb_path(), false)?;
+//!
+//! if let Some(device_id) = storage.load_device_id()? {
+//!     println!("Device ID: {}", device_id);
+//! }
+//! for ecu in storage.load_ecus()? {
+//!     println!("{} ({})", ecu.serial, ecu.hardware_id);
+//! }
+//! if let Some(targets) = storage.load_director_targets_metadata()? {
+//!     println!("{} targets assigned", targets.signed.targets.len());
+//! }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+#![warn(missing_docs)]
+
+/// Files written together to a directory or a tarball.
+pub mod archive;
+/// OLPC canonical JSON, used for signatures and key IDs.
+pub mod canonical_json;
+/// aktualizr configuration files and directories.
+pub mod config;
+/// Key types, digests and signature verification primitives.
+pub mod crypto;
+/// Validated ECU serial identifier.
+pub mod ecu_serial;
+/// Crate-wide error type and the exit codes it maps to.
+pub mod error;
+/// Import of storages written by aktualizr's legacy filesystem backend.
+pub mod fs_import;
+/// Validated ECU hardware identifier.
+pub mod hardware_identifier;
+/// Schema migrations requested with --allow-migrate, refused for now.
+pub mod migrations;
+/// Waiting for a device to be provisioned.
+pub mod provisioning;
+/// Uptane public keys and their key IDs.
+pub mod public_key;
+/// Schema versions and the features each storage supports.
+pub mod schema;
+/// Information about a Secondary ECU as stored by the Primary.
+pub mod secondary_info;
+/// Read access to the aktualizr SQLite storage.
+pub mod sqlstorage;
+/// Changes between two reads of the storage, reported as events.
+pub mod storage_events;
+/// Export of the storage content as files.
+pub mod storage_export;
+/// Notification of changes to the storage.
+pub mod storage_watcher;
+/// Redacted support bundles.
+pub mod support_bundle;
+/// Consistency checks of the stored TLS credentials against each other and the device ID.
+pub mod tls_consistency;
+/// X.509 inspection of the stored TLS credentials.
+pub mod tls_info;
+/// Expiry report of the stored metadata.
+pub mod tuf_expiry;
+/// Typed model of the TUF metadata roles.
+pub mod tuf_metadata;
+/// Image and Director repository types.
+pub mod tuf_repository_type;
+/// TUF roles, including delegated ones.
+pub mod tuf_roles;
+/// Offline verification of the stored metadata.
+pub mod tuf_verification;
+/// Metadata versions and their file names.
+pub mod tuf_version;
+/// Rows of the ECU related tables.
+pub mod types;
+/// Output buffers that leave no copy of private keys behind.
+pub mod zeroizing_writer;
@@ -1,24 +1,105 @@
-use clap::{Arg, ArgAction, Command};
+use clap::{Arg, ArgAction, ArgMatches, Command};
 use env_logger::Env;
-use log::debug;
-use rusqlite::Result;
-use sqlstorage::SQLStorage;
-
-use std::thread::sleep;
+use log::{debug, error, info, warn};
+use output::{JsonDocument, OutputFormat};
+use oxidizr::config::Config;
+use oxidizr::error::{Error, Result};
+use oxidizr::fs_import::{FsImporter, FsLayout};
+use oxidizr::provisioning::ProvisioningWaiter;
+use oxidizr::sqlstorage::SQLStorage;
+use oxidizr::storage_events::StorageState;
+use oxidizr::storage_export::StorageExporter;
+use oxidizr::storage_watcher::StorageWatcher;
+use oxidizr::support_bundle::SupportBundle;
+use oxidizr::tuf_repository_type::RepositoryType;
+use oxidizr::tuf_roles::Role;
+use report::{KeySelection, Report, TlsSelection};
+use serde_json::json;
+
+use std::fs;
+use std::io::IsTerminal;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::process::exit;
 use std::time::Duration;
 
-mod crypto;
-mod ecu_serial;
-mod hardware_identifier;
-mod public_key;
-mod secondary_info;
-mod sqlstorage;
-mod tuf_repository_type;
-mod tuf_roles;
-mod tuf_version;
-mod types;
-
-fn main() -> Result<()> {
+mod output;
+mod report;
+
+// Days ahead a role or certificate is reported as expiring soon, unless --expiry-window is given
+const DEFAULT_EXPIRY_WINDOW_DAYS: u32 = 30;
+
+// Arguments that run on their own instead of the queries
+const MODES: [&str; 4] = ["watch", "export", "support-bundle", "import-fs"];
+
+// Arguments selecting what a normal run outputs, none of which the modes take
+const QUERIES: [&str; 33] = [
+    "print-config",
+    "name-only",
+    "tls-creds",
+    "tls-root-ca",
+    "tls-cert",
+    "tls-prv-key",
+    "tls-info",
+    "tls-check",
+    "ecu-keys",
+    "ecu-keyid",
+    "ecu-pub-key",
+    "ecu-prv-key",
+    "secondary-keys",
+    "image-root",
+    "images-root",
+    "image-timestamp",
+    "images-timestamp",
+    "image-snapshot",
+    "images-snapshot",
+    "image-targets",
+    "images-targets",
+    "image-target",
+    "images-target",
+    "delegation",
+    "delegation-role",
+    "director-root",
+    "director-targets",
+    "director-target",
+    "root-version",
+    "schema",
+    "verify",
+    "expiry",
+    "expiry-window",
+];
+
+// What a mode cannot be combined with: the queries and the other modes
+fn mode_conflicts(mode: &str) -> Vec<&'static str> {
+    QUERIES
+        .into_iter()
+        .chain(MODES)
+        .filter(|name| *name != mode)
+        .collect()
+}
+
+const EXIT_CODES_HELP: &str = "Exit codes:
+  0   success
+  1   the storage cannot be read
+  2   invalid command line or configuration
+  3   no storage at the configured path
+  4   permission denied on the storage
+  5   storage schema lacks tables or columns the reader needs
+  7   device not provisioned, the requested data is not in storage yet
+  8   stored data is malformed
+  9   metadata verification failed
+  10  metadata or a certificate expires within the expiry window
+  11  metadata or a certificate expired
+  12  timed out waiting until provisioned
+  13  the storage could not be migrated
+  14  a file could not be read or written
+  15  private keys not printed, see --show-secrets
+  16  the storage has no version table or an invalid version
+
+When several problems are found, the exit code is the most severe one, in this
+order: 9, 11, 10, 8, 7, 15.";
+
+fn main() {
     let env = Env::default().filter_or("RUST_LOG", "info");
 
     env_logger::init_from_env(env);
@@ -27,6 +108,7 @@ fn main() -> Result<()> {
         .version("0.0.1")
         .author("Leonardo Held <leonardo.held@toradex.com>")
         .about("aktualizr-info command line options")
+        .after_help(EXIT_CODES_HELP)
         .arg(
             Arg::new("config")
                 .short('c')
@@ -36,6 +118,21 @@ fn main() -> Result<()> {
                 .help("Configuration file or directory")
                 .num_args(1..),
         )
+        .arg(
+            Arg::new("print-config")
+                .long("print-config")
+                .action(ArgAction::SetTrue)
+                .help("Outputs the effective configuration and the file and line that set each option. Cannot be used in combination with other arguments."),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .action(ArgAction::Set)
+                .value_name("FORMAT")
+                .help("Output format, json emits a single JSON document with the result of every requested query")
+                .value_parser([OutputFormat::TEXT, OutputFormat::JSON])
+                .default_value(OutputFormat::TEXT),
+        )
         .arg(
             Arg::new("name-only")
                 .long("name-only")
@@ -66,11 +163,23 @@ fn main() -> Result<()> {
                 .action(ArgAction::SetTrue)
                 .help("Output TLS client private key"),
         )
+        .arg(
+            Arg::new("tls-info")
+                .long("tls-info")
+                .action(ArgAction::SetTrue)
+                .help("Outputs the subject, issuer, serial, subject alternative names, validity and key algorithm of the stored root CA and client certificates, and verifies that the client certificate chains to the root CA. Exits with 9 if it does not, 10 if a certificate expires within the expiry window, 11 if one already expired"),
+        )
+        .arg(
+            Arg::new("tls-check")
+                .long("tls-check")
+                .action(ArgAction::SetTrue)
+                .help("Checks that the stored TLS credentials are PEM encoded, that the client certificate CN or a DNS SAN matches the device ID and that the client private key belongs to the client certificate. Exits with 9 if a check fails"),
+        )
         .arg(
             Arg::new("ecu-keys")
                 .long("ecu-keys")
                 .action(ArgAction::SetTrue)
-                .help("Outputs Primary's Uptane keys"),
+                .help("Outputs Primary's Uptane keys and their detected type, and checks that the private key matches the public key. Exits with 9 if it does not"),
         )
         .arg(
             Arg::new("ecu-keyid")
@@ -90,6 +199,12 @@ fn main() -> Result<()> {
                  .action(ArgAction::SetTrue)
                  .help("Outputs Primary's Uptane private key"),
          )
+        .arg(
+            Arg::new("show-secrets")
+                .long("show-secrets")
+                .action(ArgAction::SetTrue)
+                .help("Prints the private keys requested with --ecu-keys, --ecu-prv-key, --tls-creds or --tls-prv-key even when the output is not a terminal or when not running as the owner of the storage. With --export, includes the private keys in the export"),
+        )
         .arg(
             Arg::new("secondary-keys")
                 .long("secondary-keys")
@@ -126,6 +241,13 @@ fn main() -> Result<()> {
                 .action(ArgAction::SetTrue)
                 .help("Outputs metadata of Image repo Targets' delegations"),
         )
+        .arg(
+            Arg::new("delegation-role")
+                .long("delegation-role")
+                .action(ArgAction::Set)
+                .value_name("ROLE")
+                .help("Use with --delegation to only output the given delegated Targets role"),
+        )
         .arg(
             Arg::new("director-root")
                 .long("director-root")
@@ -146,17 +268,94 @@ fn main() -> Result<()> {
                 .help("Use with --image-root or --director-root to specify the version to output")
                 .value_parser(clap::value_parser!(i32)),
         )
+        .arg(
+            Arg::new("schema")
+                .long("schema")
+                .action(ArgAction::SetTrue)
+                .help("Outputs the storage schema version and which features it supports"),
+        )
+        .arg(
+            Arg::new("verify")
+                .long("verify")
+                .action(ArgAction::SetTrue)
+                .help("Verifies the stored root chain and the signatures and consistency of the Image and Director repo metadata"),
+        )
+        .arg(
+            Arg::new("expiry")
+                .long("expiry")
+                .action(ArgAction::SetTrue)
+                .help("Outputs the expiry of every role in the Image and Director repos. Exits with 10 if a role expires within the window, 11 if a role already expired"),
+        )
+        .arg(
+            Arg::new("expiry-window")
+                .long("expiry-window")
+                .action(ArgAction::Set)
+                .value_name("DAYS")
+                .help(format!("Use with --expiry or --tls-info to set how many days ahead a role or certificate is reported as expiring soon, {} by default", DEFAULT_EXPIRY_WINDOW_DAYS))
+                .value_parser(clap::value_parser!(u32)),
+        )
         .arg(
             Arg::new("allow-migrate")
                 .long("allow-migrate")
                 .action(ArgAction::SetTrue)
-                .help("Opens database in read/write mode to make possible to migrate database if needed"),
+                .help("Requests a migration of the database to the current schema version. No verified aktualizr migration scripts are bundled yet, so the database is left untouched and refused with exit code 13"),
         )
         .arg(
             Arg::new("wait-until-provisioned")
                 .long("wait-until-provisioned")
                 .action(ArgAction::SetTrue)
-                .help("Busy wait until the device is registered and has metadata, ie, is provisioned"),
+                .help("Waits until the device is registered and has metadata, ie, is provisioned"),
+        )
+        .arg(
+            Arg::new("import-fs")
+                .long("import-fs")
+                .action(ArgAction::Set)
+                .value_name("DIR")
+                .conflicts_with_all(mode_conflicts("import-fs"))
+                .help("Imports a storage written by the legacy aktualizr filesystem backend from the given directory into a new SQLite storage at the configured path. The new storage records no schema version, as it is not created by aktualizr's own schema scripts. Cannot be combined with queries or with another of --watch, --export, --support-bundle and --import-fs."),
+        )
+        .arg(
+            Arg::new("output")
+                .long("output")
+                .action(ArgAction::Set)
+                .value_name("FILE")
+                .help("Use with --import-fs to write the new storage to the given file instead of the configured path")
+                .requires("import-fs"),
+        )
+        .arg(
+            Arg::new("export")
+                .long("export")
+                .action(ArgAction::Set)
+                .value_name("PATH")
+                .conflicts_with_all(mode_conflicts("export"))
+                .help("Exports every stored metadata version, the keys, the TLS credentials and the device and ECU information in the layout of aktualizr's filesystem storage with the default configuration, which --import-fs reads back, private keys only with --show-secrets, to the given directory, to a tarball if PATH ends with .tar, .tar.gz or .tgz, or to a single JSON bundle if it ends with .json. Cannot be combined with queries or with another of --watch, --export, --support-bundle and --import-fs."),
+        )
+        .arg(
+            Arg::new("support-bundle")
+                .long("support-bundle")
+                .action(ArgAction::Set)
+                .value_name("FILE")
+                .num_args(0..=1)
+                .default_missing_value("support-bundle.tar.gz")
+                .conflicts_with_all(mode_conflicts("support-bundle"))
+                .help("Writes the device and ECU information, installed versions, installation results, metadata summaries, schema version and effective configuration to an archive to attach to support requests, support-bundle.tar.gz by default. Private keys are redacted and key material is replaced by key IDs. The archive format follows the file name as with --export. Cannot be combined with queries or with another of --watch, --export, --support-bundle and --import-fs."),
+        )
+        .arg(
+            Arg::new("watch")
+                .long("watch")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(mode_conflicts("watch"))
+                .conflicts_with("allow-migrate")
+                .help("Keeps running on the storage opened read-only, without --allow-migrate, and outputs an event whenever metadata, registration, installed versions or TLS credentials change in storage. Cannot be combined with queries or with another of --watch, --export, --support-bundle and --import-fs."),
+        )
+        .arg(
+            Arg::new("timeout")
+                .long("timeout")
+                .action(ArgAction::Set)
+                .value_name("SECONDS")
+                .help("Use with --wait-until-provisioned to give up after the given time")
+                .requires("wait-until-provisioned")
+                .value_parser(clap::value_parser!(u64)),
         )
         .arg(
             Arg::new("images-root")
@@ -207,237 +406,327 @@ fn main() -> Result<()> {
                 .help("Outputs targets.json from Director repo")
                 .hide(true),
         )
-
         .get_matches();
 
-    let mut print_default_information = true;
+    if let Err(e) = run(&matches) {
+        error!("{}", e);
+        exit(e.exit_code());
+    }
+}
 
-    let allow_migrate = matches.get_flag("allow-migrate");
-    let wait_until_provisioned = matches.get_flag("wait-until-provisioned");
-    let mut storage = SQLStorage::new("sql.db", allow_migrate)?;
-
-    if wait_until_provisioned {
-        let mut registered = false;
-        let mut has_metadata = false;
-        while !registered || !has_metadata {
-            match SQLStorage::new("sql.db", allow_migrate) {
-                Ok(new_storage) => {
-                    storage = new_storage;
-                    registered = storage.load_ecu_registered()?;
-                    has_metadata = storage.load_director_root()?.is_some();
-                }
-                Err(e) => {
-                    log::debug!("Storage exception: {}", e);
-                }
-            }
+// Roles and certificates expiring within this window are reported as expiring soon
+fn expiry_window(matches: &ArgMatches) -> chrono::Duration {
+    let days = matches
+        .get_one::<u32>("expiry-window")
+        .copied()
+        .unwrap_or(DEFAULT_EXPIRY_WINDOW_DAYS);
+    chrono::Duration::days(i64::from(days))
+}
 
-            // Busy wait
-            sleep(Duration::from_secs(1));
-        }
+// Why private keys must not be printed: they would end up in a pipe or file, where they
+// outlive the session, or be shown to someone else than the owner of the storage
+fn secrets_refusal(database_path: &Path) -> Option<String> {
+    if !std::io::stdout().is_terminal() {
+        return Some("standard output is not a terminal".to_string());
     }
-    let tls_required = matches.get_flag("tls-creds")
-        || matches.get_flag("tls-root-ca")
-        || matches.get_flag("tls-cert")
-        || matches.get_flag("tls-prv-key");
-
-    if tls_required {
-        print_default_information = false;
-
-        let mut ca: Vec<u8> = Vec::new();
-        let mut cert: Vec<u8> = Vec::new();
-        let mut pkey: Vec<u8> = Vec::new();
-
-        let tls_loaded = storage.load_tls_credentials(&mut ca, &mut cert, &mut pkey)?;
-
-        if tls_loaded {
-            if matches.get_flag("tls-creds") {
-                let ca_str = String::from_utf8(ca.clone())
-                    .unwrap_or_else(|_| "Invalid UTF-8 in CA certificate".to_string());
-                let cert_str = String::from_utf8(cert.clone())
-                    .unwrap_or_else(|_| "Invalid UTF-8 in Client certificate".to_string());
-                let pkey_str = String::from_utf8(pkey.clone())
-                    .unwrap_or_else(|_| "Invalid UTF-8 in Client private key".to_string());
-
-                println!("Root CA Certificate:");
-                println!("{}", ca_str);
-                println!("Client Certificate:");
-                println!("{}", cert_str);
-                println!("Client Private Key:");
-                println!("{}", pkey_str);
-            } else {
-                if matches.get_flag("tls-root-ca") {
-                    let ca_str = String::from_utf8(ca.clone())
-                        .unwrap_or_else(|_| "Invalid UTF-8 in CA certificate".to_string());
-                    println!("CA Certificate:");
-                    println!("{}", ca_str);
-                }
-
-                if matches.get_flag("tls-cert") {
-                    let cert_str = String::from_utf8(cert.clone())
-                        .unwrap_or_else(|_| "Invalid UTF-8 in Client certificate".to_string());
-                    println!("Client Certificate:");
-                    println!("{}", cert_str);
-                }
-
-                if matches.get_flag("tls-prv-key") {
-                    let pkey_str = String::from_utf8(pkey.clone())
-                        .unwrap_or_else(|_| "Invalid UTF-8 in Client private key".to_string());
-                    println!("Client Private Key:");
-                    println!("{}", pkey_str);
-                }
-            }
-        } else {
-            println!("TLS credentials not found or are incomplete.");
-        }
+
+    // SAFETY: geteuid has no preconditions and cannot fail
+    let uid = unsafe { libc::geteuid() };
+    match fs::metadata(database_path) {
+        Ok(metadata) if metadata.uid() == uid => None,
+        Ok(_) => Some(format!(
+            "not running as the owner of {}",
+            database_path.display()
+        )),
+        Err(e) => Some(format!(
+            "unable to check the owner of {}: {}",
+            database_path.display(),
+            e
+        )),
     }
+}
 
-    if matches.get_flag("ecu-keys")
-        || matches.get_flag("ecu-keyid")
-        || matches.get_flag("ecu-pub-key")
-        || matches.get_flag("ecu-prv-key")
-    {
-        print_default_information = false;
-
-        if let Some((pubkey, privkey)) = storage.load_primary_keys()? {
-            if matches.get_flag("ecu-keys") {
-                println!("Public Key:");
-                println!("{}", pubkey);
-                println!("Private Key:");
-                println!("{}", privkey);
-            }
+// Outputs the changes to the storage as they happen, as JSON lines with --format json
+fn watch(storage: &SQLStorage, database_path: &Path, json: bool) -> Result<()> {
+    let mut watcher = StorageWatcher::new(database_path);
+    let mut state = StorageState::load(storage)?;
+    info!("Watching {} for changes", database_path.display());
 
-            if matches.get_flag("ecu-keyid") {
-                println!("Public Key ID: {}", pubkey.key_id());
-            }
+    loop {
+        if !watcher.wait(None) {
+            continue;
+        }
 
-            if matches.get_flag("ecu-pub-key") {
-                println!("Public Key:");
-                println!("{}", pubkey);
+        let newer = match StorageState::load(storage) {
+            Ok(newer) => newer,
+            Err(e) => {
+                warn!("Unable to read storage, retrying on the next change: {}", e);
+                continue;
             }
+        };
 
-            if matches.get_flag("ecu-prv-key") {
-                println!("Private Key:");
-                println!("{}", privkey);
-            }
-        } else {
-            println!("Failed to load primary keys or keys are empty.");
-        }
-    }
-    if matches.get_flag("name-only") {
-        print_default_information = false;
-        match storage.load_device_id()? {
-            Some(device_id) => {
-                println!("{}", device_id);
-            }
-            None => {
-                println!("Device ID not found");
+        for event in state.changes(&newer) {
+            if json {
+                println!("{}", event.to_json());
+            } else {
+                println!("{}", event);
             }
         }
+        state = newer;
     }
+}
 
-    let mut secondaries = Vec::new();
-    if matches.get_flag("secondary-keys") {
-        print_default_information = false;
-        if storage.load_secondaries_info(&mut secondaries)? {
-            debug!("Secondaries loaded successfully:");
-            for secondary in secondaries {
-                println!("{}", secondary);
-            }
-        } else {
-            println!("No secondary info found.");
-        }
+// Imports a legacy filesystem storage into a new database, by default the configured one
+fn import_fs(
+    matches: &ArgMatches,
+    config: &Config,
+    directory: &str,
+    database_path: PathBuf,
+    json: bool,
+) -> Result<()> {
+    let output = matches
+        .get_one::<String>("output")
+        .map(PathBuf::from)
+        .unwrap_or(database_path);
+    let layout = FsLayout::from_config(Path::new(directory), config);
+    let summary = FsImporter::new(layout).import(&output)?;
+    if json {
+        let mut document = JsonDocument::default();
+        document.insert("import", summary.to_json());
+        println!("{}", document);
+    } else {
+        print!("{}", summary);
     }
-    if matches.get_flag("image-root") || matches.get_flag("images-root") {
-        print_default_information = false;
-        // Get the root version, if provided
-        let version = matches.get_one::<i32>("root-version").copied();
-
-        match storage.load_image_root_with_version(version)? {
-            Some(root_metadata) => println!("{}", root_metadata),
-            None => println!("Failed to load image root metadata."),
-        }
-    }
-
-    if matches.get_flag("director-root") {
-        print_default_information = false;
-        // Get the root version, if provided
-        let version = matches.get_one::<i32>("root-version").copied();
+    Ok(())
+}
 
-        match storage.load_director_root_with_version(version)? {
-            Some(root_metadata) => println!("{}", root_metadata),
-            None => println!("Failed to load director root metadata."),
-        }
+fn write_support_bundle(
+    storage: &SQLStorage,
+    config: &Config,
+    output: &str,
+    json: bool,
+) -> Result<()> {
+    let archive = SupportBundle::new(storage, config).collect()?;
+    archive.write(Path::new(output))?;
+    if json {
+        let mut document = JsonDocument::default();
+        document.insert("support_bundle", json!({ "output": output }));
+        println!("{}", document);
+    } else {
+        println!("Support bundle written to {}", output);
     }
+    Ok(())
+}
 
-    if matches.get_flag("director-targets") || matches.get_flag("director-target") {
-        match storage.load_director_targets()? {
-            Some(director_targets) => println!("{}", director_targets),
-            None => println!("Director targets metadata not found."),
-        }
+// Private keys are left out unless --show-secrets is given, as in the support bundle
+fn export(storage: &SQLStorage, output: &str, json: bool, secrets: bool) -> Result<()> {
+    if !secrets {
+        info!("Private keys are not exported, see --show-secrets");
     }
-
-    if matches.get_flag("image-snapshot") || matches.get_flag("images-snapshot") {
-        match storage.load_image_snapshot()? {
-            Some(snapshot) => println!("{}", snapshot),
-            None => println!("Image snapshot metadata not found."),
+    let archive = StorageExporter::new(storage, secrets).export()?;
+    archive.write(Path::new(output))?;
+    if json {
+        let files: Vec<String> = archive
+            .paths()
+            .map(|path| path.display().to_string())
+            .collect();
+        let mut document = JsonDocument::default();
+        document.insert("export", json!({ "output": output, "files": files }));
+        println!("{}", document);
+    } else {
+        println!("Exported to {}", output);
+        for path in archive.paths() {
+            println!("   {}", path.display());
         }
     }
+    Ok(())
+}
 
-    if matches.get_flag("image-timestamp") || matches.get_flag("images-timestamp") {
-        match storage.load_image_timestamp()? {
-            Some(timestamp) => println!("{}", timestamp),
-            None => println!("Image timestamp metadata not found."),
+fn run(matches: &ArgMatches) -> Result<()> {
+    let format = matches
+        .get_one::<String>("format")
+        .and_then(|format| format.parse::<OutputFormat>().ok())
+        .unwrap_or(OutputFormat::Text);
+    let json = format == OutputFormat::Json;
+
+    let config = match matches.get_many::<String>("config") {
+        Some(paths) => Config::load(&paths.collect::<Vec<_>>()),
+        None => Config::load_default(),
+    };
+    let config = config?;
+
+    if matches.get_flag("print-config") {
+        if json {
+            let mut document = JsonDocument::default();
+            document.insert("config", config.to_json());
+            println!("{}", document);
+        } else {
+            print!("{}", config);
         }
+        return Ok(());
     }
 
-    if matches.get_flag("image-targets")
-        || matches.get_flag("image-target")
-        || matches.get_flag("images-targets")
-        || matches.get_flag("images-target")
-    {
-        match storage.load_image_targets()? {
-            Some(images_targets) => println!("{}", images_targets),
-            None => println!("Image targets metadata not found."),
-        }
+    let database_path = config.sqldb_path();
+    debug!("Using storage {}", database_path.display());
+
+    if let Some(directory) = matches.get_one::<String>("import-fs") {
+        return import_fs(matches, &config, directory, database_path, json);
     }
 
-    // Print general information if user does not provide any argument.
-    if print_default_information {
-        match storage.load_device_id()? {
-            Some(device_id) => {
-                println!("Device ID: {}", device_id);
-            }
-            None => {
-                println!("Device ID not found");
+    let allow_migrate = matches.get_flag("allow-migrate");
+    let storage = if matches.get_flag("wait-until-provisioned") {
+        let timeout = matches
+            .get_one::<u64>("timeout")
+            .copied()
+            .map(Duration::from_secs);
+        let waiter = ProvisioningWaiter::new(&database_path, allow_migrate, timeout);
+        waiter.wait(|status| {
+            if !status.is_provisioned() {
+                info!("Waiting until provisioned, {}", status);
             }
-        }
+        })?
+    } else {
+        SQLStorage::new(&database_path, allow_migrate)?
+    };
 
-        let ecus = storage.load_ecus()?;
-        let mut secondaries = Vec::new();
+    if matches.get_flag("watch") {
+        return watch(&storage, &database_path, json);
+    }
+    if let Some(output) = matches.get_one::<String>("support-bundle") {
+        return write_support_bundle(&storage, &config, output, json);
+    }
+    if let Some(output) = matches.get_one::<String>("export") {
+        return export(&storage, output, json, matches.get_flag("show-secrets"));
+    }
 
-        for ecu in ecus {
-            if ecu.is_primary {
-                println!("Primary ECU serial ID: {}", ecu.serial);
-                println!("Primary ECU hardware ID: {}", ecu.hardware_id);
-            } else {
-                secondaries.push(ecu);
-            }
+    let mut report = Report::new(&storage, json);
+    let flag = |names: &[&str]| names.iter().any(|name| matches.get_flag(name));
+
+    // Private keys are held back unless it is safe to print them or the user insists
+    let mut show_private = true;
+    if flag(&["tls-creds", "tls-prv-key", "ecu-keys", "ecu-prv-key"])
+        && !matches.get_flag("show-secrets")
+    {
+        if let Some(reason) = secrets_refusal(&database_path) {
+            show_private = false;
+            report.fail(Error::SecretsRefused(reason));
         }
+    }
 
-        if !secondaries.is_empty() {
-            println!("Secondaries:");
-            for (index, secondary) in secondaries.iter().enumerate() {
-                println!(
-                    "{}) ID: {}, serial ID: {}",
-                    index + 1,
-                    secondary.id,
-                    secondary.serial
-                );
-                println!("   hardware ID: {}", secondary.hardware_id);
-                println!("   no details about installed nor pending images");
-            }
+    let tls = TlsSelection {
+        all: flag(&["tls-creds"]),
+        ca: flag(&["tls-root-ca"]),
+        cert: flag(&["tls-cert"]),
+        pkey: flag(&["tls-prv-key"]),
+    };
+    let keys = KeySelection {
+        all: flag(&["ecu-keys"]),
+        key_id: flag(&["ecu-keyid"]),
+        public: flag(&["ecu-pub-key"]),
+        private: flag(&["ecu-prv-key"]),
+    };
+    let root_version = matches.get_one::<i32>("root-version").copied();
+    let metadata = [
+        (
+            flag(&["image-root", "images-root"]),
+            RepositoryType::image(),
+            Role::root(),
+            root_version,
+        ),
+        (
+            flag(&["director-root"]),
+            RepositoryType::director(),
+            Role::root(),
+            root_version,
+        ),
+        (
+            flag(&["director-targets", "director-target"]),
+            RepositoryType::director(),
+            Role::targets(),
+            None,
+        ),
+        (
+            flag(&["image-snapshot", "images-snapshot"]),
+            RepositoryType::image(),
+            Role::snapshot(),
+            None,
+        ),
+        (
+            flag(&["image-timestamp", "images-timestamp"]),
+            RepositoryType::image(),
+            Role::timestamp(),
+            None,
+        ),
+        (
+            flag(&[
+                "image-targets",
+                "image-target",
+                "images-targets",
+                "images-target",
+            ]),
+            RepositoryType::image(),
+            Role::targets(),
+            None,
+        ),
+    ];
+
+    // The general information is only shown when no query is requested
+    let mut queried = false;
+
+    if tls.all || tls.ca || tls.cert || tls.pkey {
+        queried = true;
+        report.tls_credentials(&tls, show_private)?;
+    }
+    if flag(&["tls-info"]) {
+        queried = true;
+        report.tls_info(expiry_window(matches))?;
+    }
+    if flag(&["tls-check"]) {
+        queried = true;
+        report.tls_check()?;
+    }
+    if keys.all || keys.key_id || keys.public || keys.private {
+        queried = true;
+        report.primary_keys(&keys, show_private)?;
+    }
+    if flag(&["name-only"]) {
+        queried = true;
+        report.device_id()?;
+    }
+    if flag(&["secondary-keys"]) {
+        queried = true;
+        report.secondaries()?;
+    }
+    for (requested, repo, role, version) in metadata {
+        if requested {
+            queried = true;
+            report.metadata(repo, role, version)?;
         }
     }
+    if flag(&["delegation"]) {
+        queried = true;
+        report.delegations(
+            matches
+                .get_one::<String>("delegation-role")
+                .map(String::as_str),
+        )?;
+    }
+    if flag(&["schema"]) {
+        queried = true;
+        report.schema();
+    }
+    if flag(&["verify"]) {
+        queried = true;
+        report.verification()?;
+    }
+    if flag(&["expiry"]) {
+        queried = true;
+        report.expiry(expiry_window(matches))?;
+    }
 
-    Ok(())
+    if !queried {
+        report.device_information()?;
+    }
+
+    report.finish()
 }
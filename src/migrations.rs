@@ -0,0 +1,37 @@
+use crate::error::{Error, Result};
+use crate::schema::SchemaInfo;
+use log::debug;
+use rusqlite::Connection;
+
+/// Migrating a storage means running aktualizr's own migration scripts, so that the rollback
+/// scripts an older aktualizr relies on are recorded too. None are bundled yet: they have to be
+/// taken byte for byte from aktualizr's config/sql/migration, starting from version 0. Until
+/// then every storage is refused and left untouched.
+pub struct Migrator<'a> {
+    conn: &'a Connection,
+}
+
+impl<'a> Migrator<'a> {
+    /// Migrator of the storage open on the connection.
+    pub fn new(conn: &'a Connection) -> Self {
+        Migrator { conn }
+    }
+
+    /// Refuses to migrate, after making sure the storage has a schema at all. Nothing is
+    /// written to the storage and no backup is taken.
+    pub fn migrate(&self) -> Result<()> {
+        let version = SchemaInfo::load_version(self.conn)?;
+        debug!(
+            "Refusing to migrate storage at schema version {:?}",
+            version
+        );
+        let version = match version {
+            Some(version) => format!("schema version {}", version),
+            None => "no recorded schema version".to_string(),
+        };
+        Err(Error::Migration(format!(
+            "no verified aktualizr migration scripts are bundled, storage with {} left untouched",
+            version
+        )))
+    }
+}
@@ -0,0 +1,112 @@
+use log::warn;
+use oxidizr::error::{Error, Result};
+use oxidizr::zeroizing_writer::{wipe_json, ZeroizingWriter};
+use serde_json::{Map, Value};
+use std::fmt;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+impl OutputFormat {
+    pub const TEXT: &'static str = "text";
+    pub const JSON: &'static str = "json";
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            Self::TEXT => Ok(OutputFormat::Text),
+            Self::JSON => Ok(OutputFormat::Json),
+            _ => Err(format!("Unknown output format: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let format_str = match *self {
+            OutputFormat::Text => Self::TEXT,
+            OutputFormat::Json => Self::JSON,
+        };
+        write!(f, "{}", format_str)
+    }
+}
+
+// Collects the results of every requested query so they are emitted as a single JSON document.
+// It can hold private keys when secrets are shown, so its strings are wiped on drop.
+#[derive(Debug, Default)]
+pub struct JsonDocument {
+    root: Map<String, Value>,
+}
+
+impl JsonDocument {
+    pub fn insert(&mut self, key: &str, value: Value) {
+        self.root.insert(key.to_string(), value);
+    }
+
+    // Inserts the value under root[section][key], creating the section if needed
+    pub fn insert_nested(&mut self, section: &str, key: &str, value: Value) {
+        let entry = self
+            .root
+            .entry(section.to_string())
+            .or_insert_with(|| Value::Object(Map::new()));
+        if let Value::Object(map) = entry {
+            map.insert(key.to_string(), value);
+        }
+    }
+
+    // Prints the document to stdout through a buffer that wipes every copy it makes while
+    // growing, unlike the string Display would build
+    pub fn print(&self) -> Result<()> {
+        let mut buffer = ZeroizingWriter::new();
+        serde_json::to_writer_pretty(&mut buffer, &self.root)
+            .map_err(io::Error::from)
+            .and_then(|()| buffer.write_all(b"\n"))
+            .map_err(|error| Error::Io {
+                path: PathBuf::from("<stdout>"),
+                error,
+            })?;
+        io::stdout()
+            .lock()
+            .write_all(&buffer)
+            .map_err(|error| Error::Io {
+                path: PathBuf::from("<stdout>"),
+                error,
+            })
+    }
+
+    // Metadata is embedded as parsed JSON, it only falls back to a plain string if the stored
+    // blob is not valid JSON
+    pub fn metadata(metadata: Option<String>) -> Value {
+        match metadata {
+            Some(metadata) => serde_json::from_str(&metadata).unwrap_or_else(|e| {
+                warn!("Stored metadata is not valid JSON: {}", e);
+                Value::String(metadata)
+            }),
+            None => Value::Null,
+        }
+    }
+}
+
+impl Drop for JsonDocument {
+    fn drop(&mut self) {
+        for value in self.root.values_mut() {
+            wipe_json(value);
+        }
+    }
+}
+
+impl fmt::Display for JsonDocument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let document = serde_json::to_string_pretty(&self.root).map_err(|_| fmt::Error)?;
+        write!(f, "{}", document)
+    }
+}
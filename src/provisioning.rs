@@ -0,0 +1,122 @@
+use crate::error::{Error, Result};
+use crate::sqlstorage::SQLStorage;
+use crate::storage_watcher::StorageWatcher;
+use log::debug;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// What is already in place of what a provisioned device has
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProvisioningStatus {
+    /// Whether the storage exists and can be read.
+    pub storage: bool,
+    /// Whether the device is registered.
+    pub registered: bool,
+    /// Whether Director root metadata is stored.
+    pub director_root: bool,
+}
+
+impl ProvisioningStatus {
+    /// Status of a storage that could be opened.
+    pub fn load(storage: &SQLStorage) -> Result<Self> {
+        Ok(ProvisioningStatus {
+            storage: true,
+            registered: storage.load_ecu_registered()?,
+            director_root: storage.load_director_root()?.is_some(),
+        })
+    }
+
+    /// Whether everything a provisioned device has is in place.
+    pub fn is_provisioned(&self) -> bool {
+        self.storage && self.registered && self.director_root
+    }
+
+    /// Names of what is not in place yet.
+    pub fn missing(&self) -> Vec<&'static str> {
+        let mut missing = Vec::new();
+        if !self.storage {
+            missing.push("storage");
+        }
+        if !self.registered {
+            missing.push("registration");
+        }
+        if !self.director_root {
+            missing.push("Director root metadata");
+        }
+        missing
+    }
+}
+
+impl fmt::Display for ProvisioningStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_provisioned() {
+            write!(f, "provisioned")
+        } else {
+            write!(f, "missing {}", self.missing().join(", "))
+        }
+    }
+}
+
+/// Waits for a device to be provisioned, watching its storage for changes.
+pub struct ProvisioningWaiter {
+    database_path: PathBuf,
+    allow_migrate: bool,
+    timeout: Option<Duration>,
+}
+
+impl ProvisioningWaiter {
+    /// Waiter for the storage at the path, giving up after the timeout if there is one.
+    pub fn new(database_path: &Path, allow_migrate: bool, timeout: Option<Duration>) -> Self {
+        ProvisioningWaiter {
+            database_path: database_path.to_path_buf(),
+            allow_migrate,
+            timeout,
+        }
+    }
+
+    /// Waits until the device is registered and has Director root metadata and returns the
+    /// storage. The progress callback gets every status change, starting with the initial one.
+    /// A storage that does not exist yet or is still being set up is waited for, errors that
+    /// waiting cannot fix are returned right away.
+    pub fn wait(&self, mut progress: impl FnMut(&ProvisioningStatus)) -> Result<SQLStorage> {
+        let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        let mut watcher = StorageWatcher::new(&self.database_path);
+        let mut last_status = None;
+
+        loop {
+            let (status, storage) = match self.check() {
+                Ok((status, storage)) => (status, Some(storage)),
+                Err(e @ (Error::PermissionDenied(_) | Error::Migration(_))) => return Err(e),
+                Err(e) => {
+                    debug!("Storage not ready: {}", e);
+                    (ProvisioningStatus::default(), None)
+                }
+            };
+
+            if last_status != Some(status) {
+                progress(&status);
+                last_status = Some(status);
+            }
+            if let (true, Some(storage)) = (status.is_provisioned(), storage) {
+                return Ok(storage);
+            }
+
+            let remaining =
+                deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+            if remaining.is_some_and(|remaining| remaining.is_zero()) || !watcher.wait(remaining) {
+                return Err(Error::Timeout(format!(
+                    "device not provisioned after {} seconds, {}",
+                    self.timeout.unwrap_or_default().as_secs(),
+                    status
+                )));
+            }
+        }
+    }
+
+    fn check(&self) -> Result<(ProvisioningStatus, SQLStorage)> {
+        let storage = SQLStorage::new(&self.database_path, self.allow_migrate)?;
+        let status = ProvisioningStatus::load(&storage)?;
+        Ok((status, storage))
+    }
+}
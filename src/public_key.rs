@@ -1,10 +1,16 @@
-use crate::crypto::{Crypto, KeyType};
+use crate::canonical_json::CanonicalJson;
+use crate::crypto::{Crypto, KeyType, SignatureEncoding, SignatureScheme, VerificationError};
 use serde_json::Value;
+use std::borrow::Cow;
 use std::error::Error;
 use std::fmt;
 use std::fs;
 use std::path::Path;
 
+/// An Uptane public key with its type.
+///
+/// Keys come from the Primary's storage, a Secondary's record or root metadata. RSA and
+/// ECDSA keys are PEM encoded, Ed25519 keys hex or PEM encoded.
 #[derive(Debug, Clone)]
 pub struct PublicKey {
     value: String,
@@ -12,6 +18,7 @@ pub struct PublicKey {
 }
 
 impl PublicKey {
+    /// Key with the given value and type, taken as is.
     pub fn new(value: &str, key_type: KeyType) -> Self {
         PublicKey {
             value: value.to_string(),
@@ -19,12 +26,39 @@ impl PublicKey {
         }
     }
 
+    /// The key as stored.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Type of the key.
+    pub fn key_type(&self) -> &KeyType {
+        &self.key_type
+    }
+
+    /// Public key with its type detected from a stored value, which is kept as it is.
+    pub fn detect(value: &str) -> Self {
+        PublicKey {
+            value: value.to_string(),
+            key_type: Crypto::identify_key_type(value),
+        }
+    }
+
+    /// Reads a public key from a file, detecting its type.
     pub fn from_path(path: &Path) -> Result<Self, Box<dyn Error>> {
-        let value = fs::read_to_string(path)?;
-        let key_type = Crypto::identify_rsa_key_type(&value)?;
-        Ok(PublicKey { value, key_type })
+        let key = Self::detect(&fs::read_to_string(path)?);
+        if key.key_type == KeyType::Unknown {
+            return Err(format!(
+                "{} is not an RSA, Ed25519 or ECDSA P-256 public key",
+                path.display()
+            )
+            .into());
+        }
+        Ok(key)
     }
 
+    /// Key from its Uptane JSON form, `{"keytype": ..., "keyval": {"public": ...}}`. The
+    /// type is checked against the key value.
     pub fn from_json(uptane_json: &Value) -> Result<Self, Box<dyn Error>> {
         let keytype = uptane_json["keytype"]
             .as_str()
@@ -38,6 +72,10 @@ impl PublicKey {
         let key_type = match keytype.as_str() {
             "ed25519" => KeyType::Ed25519,
             "rsa" => Crypto::identify_rsa_key_type(&keyvalue)?,
+            "ecdsa-sha2-nistp256" | "ecdsa" => match Crypto::identify_ec_key_type(&keyvalue)? {
+                KeyType::EcdsaP256 => KeyType::EcdsaP256,
+                _ => return Err("ECDSA key is not on the NIST P-256 curve".into()),
+            },
             _ => KeyType::Unknown,
         };
 
@@ -47,35 +85,97 @@ impl PublicKey {
         })
     }
 
-    pub fn verify_signature(&self, signature: &str, message: &str) -> bool {
-        match self.key_type {
-            KeyType::Ed25519 => Crypto::ed25519_verify(&self.value, signature, message),
-            KeyType::Rsa2048 | KeyType::Rsa3072 | KeyType::Rsa4096 => {
-                Crypto::rsa_pss_verify(&self.value, signature, message)
+    /// Verifies a signature made with the given scheme over the message. The scheme has to be
+    /// one this key signs with: a signature claiming another scheme is rejected rather than
+    /// checked, so an RSA key is never used to check an ECDSA signature or the other way round.
+    pub fn verify(
+        &self,
+        scheme: SignatureScheme,
+        signature: &str,
+        encoding: SignatureEncoding,
+        message: &[u8],
+    ) -> Result<(), VerificationError> {
+        if !scheme.supports(&self.key_type) {
+            return Err(VerificationError::SchemeMismatch {
+                scheme,
+                key_type: self.key_type.clone(),
+            });
+        }
+        let signature = encoding.decode(signature)?;
+        Crypto::verify(scheme, &self.value, &signature, message)
+    }
+
+    /// Checks that a private key belongs to this public key by signing a random challenge with
+    /// it and verifying the signature.
+    pub fn verify_private_key(&self, private_key: &str) -> Result<(), String> {
+        let mut challenge = [0u8; 32];
+        openssl::rand::rand_bytes(&mut challenge).map_err(|e| e.to_string())?;
+        let challenge = hex::encode(challenge);
+
+        let signature = Crypto::sign(&self.key_type, private_key, &challenge)
+            .map_err(|e| format!("private key cannot sign as {}: {}", self.key_type, e))?;
+        let scheme = SignatureScheme::for_key_type(&self.key_type)
+            .ok_or_else(|| format!("no signature scheme for {} keys", self.key_type))?;
+        match self.verify(
+            scheme,
+            &hex::encode(signature),
+            SignatureEncoding::Hex,
+            challenge.as_bytes(),
+        ) {
+            Ok(()) => Ok(()),
+            Err(VerificationError::BadSignature) => {
+                Err("private key does not match the public key".to_string())
             }
-            _ => false,
+            Err(e) => Err(e.to_string()),
         }
     }
 
+    /// The key material as DER encoded SubjectPublicKeyInfo, identical for every encoding of
+    /// the same key. `None` if the key cannot be parsed.
+    pub fn to_der(&self) -> Option<Vec<u8>> {
+        Crypto::public_key_der(&self.value)
+    }
+
+    /// The key in its Uptane JSON form, as found in root metadata.
     pub fn to_uptane(&self) -> Value {
-        let mut res = serde_json::json!({});
+        serde_json::json!({
+            "keytype": self.uptane_key_type(),
+            "keyval": { "public": self.uptane_value() },
+        })
+    }
+
+    /// The key ID: the SHA-256 of the canonical JSON form of [`PublicKey::to_uptane`],
+    /// matching the IDs used in root.json and by the backend.
+    //
+    // That form only holds strings, so it is written out here with its keys in canonical
+    // order and computing the ID cannot fail.
+    pub fn key_id(&self) -> String {
+        let canonical = format!(
+            "{{\"keytype\":{},\"keyval\":{{\"public\":{}}}}}",
+            CanonicalJson::string(self.uptane_key_type()),
+            CanonicalJson::string(&self.uptane_value())
+        );
+        Crypto::sha256digest_hex(&canonical)
+    }
+
+    // Ed25519 keys are hex encoded in Uptane metadata, so that their key ID matches the
+    // backend's, whatever encoding they were stored in
+    fn uptane_value(&self) -> Cow<'_, str> {
         match self.key_type {
-            KeyType::Rsa2048 | KeyType::Rsa3072 | KeyType::Rsa4096 => {
-                res["keytype"] = Value::String("RSA".to_string());
-            }
-            KeyType::Ed25519 => {
-                res["keytype"] = Value::String("ED25519".to_string());
-            }
-            KeyType::Unknown => {
-                res["keytype"] = Value::String("unknown".to_string());
-            }
+            KeyType::Ed25519 => Crypto::ed25519_public_key_hex(&self.value)
+                .map(Cow::Owned)
+                .unwrap_or(Cow::Borrowed(&self.value)),
+            _ => Cow::Borrowed(&self.value),
         }
-        res["keyval"]["public"] = Value::String(self.value.clone());
-        res
     }
 
-    pub fn key_id(&self) -> String {
-        Crypto::sha256digest_hex(&self.value)
+    fn uptane_key_type(&self) -> &'static str {
+        match self.key_type {
+            KeyType::Rsa2048 | KeyType::Rsa3072 | KeyType::Rsa4096 | KeyType::Rsa(_) => "RSA",
+            KeyType::Ed25519 => "ED25519",
+            KeyType::EcdsaP256 => "ecdsa-sha2-nistp256",
+            KeyType::Unknown => "unknown",
+        }
     }
 }
 
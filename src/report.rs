@@ -0,0 +1,604 @@
+use crate::output::JsonDocument;
+use log::{debug, error};
+use oxidizr::error::{Error, Result};
+use oxidizr::secondary_info::SecondaryInfo;
+use oxidizr::sqlstorage::SQLStorage;
+use oxidizr::tls_consistency::TlsConsistencyChecker;
+use oxidizr::tls_info::{ChainStatus, TlsInspector};
+use oxidizr::tuf_expiry::{ExpiryChecker, ExpiryStatus};
+use oxidizr::tuf_repository_type::RepositoryType;
+use oxidizr::tuf_roles::Role;
+use oxidizr::tuf_verification::MetadataVerifier;
+use oxidizr::types::EcuVersions;
+use serde_json::{json, Map, Value};
+use zeroize::Zeroizing;
+
+// Which TLS credentials --tls-creds, --tls-root-ca, --tls-cert and --tls-prv-key ask for
+pub struct TlsSelection {
+    pub all: bool,
+    pub ca: bool,
+    pub cert: bool,
+    pub pkey: bool,
+}
+
+// What --ecu-keys, --ecu-keyid, --ecu-pub-key and --ecu-prv-key ask for
+pub struct KeySelection {
+    pub all: bool,
+    pub key_id: bool,
+    pub public: bool,
+    pub private: bool,
+}
+
+// Runs the queries requested on the command line against one storage. Each query prints its
+// result, or adds it to the JSON document printed by finish(), and records why the exit code
+// is not 0.
+pub struct Report<'a> {
+    storage: &'a SQLStorage,
+    json: bool,
+    document: JsonDocument,
+    failure: Option<Error>,
+}
+
+impl<'a> Report<'a> {
+    pub fn new(storage: &'a SQLStorage, json: bool) -> Self {
+        Report {
+            storage,
+            json,
+            document: JsonDocument::default(),
+            failure: None,
+        }
+    }
+
+    // Keeps the most severe failure to decide the exit code, the first one among equally
+    // severe ones. The others are only logged.
+    pub fn fail(&mut self, e: Error) {
+        match self.failure.take() {
+            Some(failure) if failure.severity() >= e.severity() => {
+                error!("{}", e);
+                self.failure = Some(failure);
+            }
+            Some(failure) => {
+                error!("{}", failure);
+                self.failure = Some(e);
+            }
+            None => self.failure = Some(e),
+        }
+    }
+
+    // Prints the JSON document, and fails with the recorded failure if there is one
+    pub fn finish(self) -> Result<()> {
+        if self.json {
+            self.document.print()?;
+        }
+
+        match self.failure {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    pub fn tls_credentials(&mut self, selection: &TlsSelection, show_private: bool) -> Result<()> {
+        let mut ca: Vec<u8> = Vec::new();
+        let mut cert: Vec<u8> = Vec::new();
+        let mut pkey = Zeroizing::new(Vec::new());
+
+        let tls_loaded = self
+            .storage
+            .load_tls_credentials(&mut ca, &mut cert, &mut pkey)?;
+        let show_ca = selection.all || selection.ca;
+        let show_cert = selection.all || selection.cert;
+        let show_pkey = (selection.all || selection.pkey) && show_private;
+
+        if !tls_loaded {
+            if self.json {
+                self.document.insert("tls", Value::Null);
+            }
+            self.fail(Error::NotProvisioned(
+                "TLS credentials not found or are incomplete".to_string(),
+            ));
+        } else if self.json {
+            if show_ca {
+                self.document.insert_nested(
+                    "tls",
+                    "ca_cert",
+                    json!(printable(&ca, "Invalid UTF-8 in CA certificate")),
+                );
+            }
+            if show_cert {
+                self.document.insert_nested(
+                    "tls",
+                    "client_cert",
+                    json!(printable(&cert, "Invalid UTF-8 in Client certificate")),
+                );
+            }
+            if show_pkey {
+                self.document.insert_nested(
+                    "tls",
+                    "client_pkey",
+                    json!(printable(&pkey, "Invalid UTF-8 in Client private key")),
+                );
+            }
+        } else {
+            if show_ca {
+                println!(
+                    "{}",
+                    if selection.all {
+                        "Root CA Certificate:"
+                    } else {
+                        "CA Certificate:"
+                    }
+                );
+                println!("{}", printable(&ca, "Invalid UTF-8 in CA certificate"));
+            }
+            if show_cert {
+                println!("Client Certificate:");
+                println!(
+                    "{}",
+                    printable(&cert, "Invalid UTF-8 in Client certificate")
+                );
+            }
+            if show_pkey {
+                println!("Client Private Key:");
+                println!(
+                    "{}",
+                    printable(&pkey, "Invalid UTF-8 in Client private key")
+                );
+            }
+        }
+        Ok(())
+    }
+
+    pub fn tls_info(&mut self, expiry_window: chrono::Duration) -> Result<()> {
+        let mut ca: Vec<u8> = Vec::new();
+        let mut cert: Vec<u8> = Vec::new();
+        let mut pkey = Zeroizing::new(Vec::new());
+
+        if !self
+            .storage
+            .load_tls_credentials(&mut ca, &mut cert, &mut pkey)?
+        {
+            if self.json {
+                self.document.insert("tls_info", Value::Null);
+            }
+            self.fail(Error::NotProvisioned(
+                "TLS credentials not found or are incomplete".to_string(),
+            ));
+            return Ok(());
+        }
+
+        let inspector = TlsInspector::new(expiry_window);
+        let info = inspector.inspect(&ca, &cert)?;
+
+        if let ChainStatus::Failed(e) = &info.chain {
+            self.fail(Error::Verification(format!(
+                "client certificate does not chain to the root CA: {}",
+                e
+            )));
+        }
+        let expired = info.count(&ExpiryStatus::Expired);
+        let expiring_soon = info.count(&ExpiryStatus::ExpiringSoon);
+        if expired > 0 {
+            self.fail(Error::MetadataExpired(format!(
+                "{} TLS certificates expired",
+                expired
+            )));
+        } else if expiring_soon > 0 {
+            self.fail(Error::MetadataExpiringSoon(format!(
+                "{} TLS certificates expire soon",
+                expiring_soon
+            )));
+        }
+
+        if self.json {
+            self.document.insert("tls_info", info.to_json());
+        } else {
+            print!("{}", info);
+        }
+        Ok(())
+    }
+
+    pub fn tls_check(&mut self) -> Result<()> {
+        let results = match TlsConsistencyChecker::new(self.storage).check()? {
+            Some(results) => results,
+            None => {
+                if self.json {
+                    self.document.insert("tls_check", Value::Null);
+                }
+                self.fail(Error::NotProvisioned(
+                    "TLS credentials not found or are incomplete".to_string(),
+                ));
+                return Ok(());
+            }
+        };
+
+        let failed = results.iter().filter(|result| !result.passed()).count();
+        let passed = failed == 0;
+        if !passed {
+            self.fail(Error::Verification(format!(
+                "{} of {} TLS checks failed",
+                failed,
+                results.len()
+            )));
+        }
+
+        if self.json {
+            let checks: Vec<Value> = results.iter().map(|result| result.to_json()).collect();
+            self.document
+                .insert_nested("tls_check", "checks", Value::Array(checks));
+            self.document
+                .insert_nested("tls_check", "passed", json!(passed));
+        } else {
+            for result in &results {
+                println!("{}", result);
+            }
+            if passed {
+                println!("TLS credentials check passed.");
+            } else {
+                println!("TLS credentials check failed.");
+            }
+        }
+        Ok(())
+    }
+
+    pub fn primary_keys(&mut self, selection: &KeySelection, show_private: bool) -> Result<()> {
+        let show_pub = selection.all || selection.public;
+        let show_prv = (selection.all || selection.private) && show_private;
+
+        let keys = self.storage.load_primary_keys()?;
+        // The key pair check reveals nothing secret, it runs even when the private key is held back
+        let key_pair = match &keys {
+            Some((pubkey, privkey)) if selection.all => {
+                let result = pubkey.verify_private_key(privkey);
+                if let Err(e) = &result {
+                    self.fail(Error::Verification(format!("Primary key pair: {}", e)));
+                }
+                Some(result)
+            }
+            _ => None,
+        };
+
+        match keys {
+            Some((pubkey, privkey)) if self.json => {
+                if show_pub {
+                    self.document
+                        .insert_nested("primary_keys", "public_key", pubkey.to_uptane());
+                }
+                if let Some(key_pair) = &key_pair {
+                    self.document.insert_nested(
+                        "primary_keys",
+                        "key_type",
+                        json!(pubkey.key_type().to_string()),
+                    );
+                    self.document.insert_nested(
+                        "primary_keys",
+                        "key_pair_valid",
+                        json!(key_pair.is_ok()),
+                    );
+                }
+                if show_prv {
+                    self.document.insert_nested(
+                        "primary_keys",
+                        "private_key",
+                        json!(privkey.as_str()),
+                    );
+                }
+                if selection.key_id {
+                    self.document
+                        .insert_nested("primary_keys", "key_id", json!(pubkey.key_id()));
+                }
+            }
+            Some((pubkey, privkey)) => {
+                if selection.all {
+                    println!("Key Type: {}", pubkey.key_type());
+                    if let Some(key_pair) = &key_pair {
+                        match key_pair {
+                            Ok(()) => println!("Key Pair: OK"),
+                            Err(e) => println!("Key Pair: FAILED: {}", e),
+                        }
+                    }
+                    println!("Public Key:");
+                    println!("{}", pubkey);
+                    if show_prv {
+                        println!("Private Key:");
+                        println!("{}", privkey.as_str());
+                    }
+                }
+
+                if selection.key_id {
+                    println!("Public Key ID: {}", pubkey.key_id());
+                }
+
+                if selection.public {
+                    println!("Public Key:");
+                    println!("{}", pubkey);
+                }
+
+                if selection.private && show_prv {
+                    println!("Private Key:");
+                    println!("{}", privkey.as_str());
+                }
+            }
+            None => {
+                if self.json {
+                    self.document.insert("primary_keys", Value::Null);
+                }
+                self.fail(Error::NotProvisioned(
+                    "primary keys not found or are empty".to_string(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn device_id(&mut self) -> Result<()> {
+        let device_id = self.storage.load_device_id()?;
+        if self.json {
+            self.document.insert("device_id", json!(device_id));
+        } else if let Some(device_id) = &device_id {
+            println!("{}", device_id);
+        }
+        if device_id.is_none() {
+            self.fail(Error::NotProvisioned("device ID not found".to_string()));
+        }
+        Ok(())
+    }
+
+    pub fn secondaries(&mut self) -> Result<()> {
+        let mut secondaries = Vec::new();
+        let found = self.storage.load_secondaries_info(&mut secondaries)?;
+        if self.json {
+            let secondaries: Vec<Value> = secondaries.iter().map(SecondaryInfo::to_json).collect();
+            self.document
+                .insert("secondaries", Value::Array(secondaries));
+        } else {
+            debug!("Secondaries loaded successfully:");
+            for secondary in secondaries {
+                println!("{}", secondary);
+            }
+        }
+
+        if !found {
+            self.fail(Error::NotProvisioned("no secondary info found".to_string()));
+        }
+        Ok(())
+    }
+
+    // Prints the metadata or adds it to the JSON document. Metadata missing from storage means
+    // the device has not fetched it yet.
+    pub fn metadata(
+        &mut self,
+        repo: RepositoryType,
+        role: Role,
+        version: Option<i32>,
+    ) -> Result<()> {
+        let metadata = self.storage.load_metadata(repo, role.clone(), version)?;
+        let repo = match repo {
+            repo if repo == RepositoryType::director() => "director",
+            _ => "image",
+        };
+        let role = role.to_string();
+
+        let found = metadata.is_some();
+        if self.json {
+            self.document
+                .insert_nested(repo, &role, JsonDocument::metadata(metadata));
+        } else if let Some(metadata) = metadata {
+            println!("{}", metadata);
+        }
+
+        if !found {
+            self.fail(Error::NotProvisioned(format!(
+                "{} {} metadata not found",
+                repo, role
+            )));
+        }
+        Ok(())
+    }
+
+    // One delegation when a role is given, all of them otherwise
+    pub fn delegations(&mut self, role_name: Option<&str>) -> Result<()> {
+        let delegations = match role_name {
+            Some(role_name) => {
+                let role = Role::delegation(role_name);
+                match self.storage.load_delegation(&role)? {
+                    Some(delegation) => vec![(role, delegation)],
+                    None => {
+                        self.fail(Error::NotProvisioned(format!(
+                            "delegation {} not found",
+                            role_name
+                        )));
+                        Vec::new()
+                    }
+                }
+            }
+            None => {
+                let delegations = self.storage.load_all_delegations()?;
+                if delegations.is_empty() && !self.json {
+                    println!("Image repo Targets have no delegations.");
+                }
+                delegations
+            }
+        };
+
+        if self.json {
+            let mut roles = Map::new();
+            for (role, delegation) in delegations {
+                roles.insert(role.to_string(), JsonDocument::metadata(Some(delegation)));
+            }
+            self.document.insert("delegations", Value::Object(roles));
+        } else if role_name.is_some() && delegations.len() == 1 {
+            println!("{}", delegations[0].1);
+        } else {
+            for (role, delegation) in delegations {
+                println!("{}: {}", role, delegation);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn schema(&mut self) {
+        if self.json {
+            self.document
+                .insert("schema", self.storage.schema().to_json());
+        } else {
+            print!("{}", self.storage.schema());
+        }
+    }
+
+    pub fn verification(&mut self) -> Result<()> {
+        let verifier = MetadataVerifier::new(self.storage);
+        let mut results = verifier.verify_root_chain(RepositoryType::image())?;
+        results.extend(verifier.verify_image_metadata()?);
+        results.extend(verifier.verify_root_chain(RepositoryType::director())?);
+        results.extend(verifier.verify_director_metadata()?);
+
+        let failed = results.iter().filter(|result| !result.passed()).count();
+        let passed = failed == 0;
+        if !passed {
+            self.fail(Error::Verification(format!(
+                "{} of {} checks failed",
+                failed,
+                results.len()
+            )));
+        }
+
+        if self.json {
+            let checks: Vec<Value> = results.iter().map(|result| result.to_json()).collect();
+            self.document
+                .insert_nested("verification", "checks", Value::Array(checks));
+            self.document
+                .insert_nested("verification", "passed", json!(passed));
+        } else {
+            for result in &results {
+                println!("{}", result);
+            }
+            if passed {
+                println!("Metadata verification passed.");
+            } else {
+                println!("Metadata verification failed.");
+            }
+        }
+        Ok(())
+    }
+
+    pub fn expiry(&mut self, expiry_window: chrono::Duration) -> Result<()> {
+        let checker = ExpiryChecker::new(self.storage, expiry_window);
+        let entries = checker.check_all()?;
+
+        let count = |status: ExpiryStatus| {
+            entries
+                .iter()
+                .filter(|entry| entry.status == status)
+                .count()
+        };
+        let expired = count(ExpiryStatus::Expired);
+        let expiring_soon = count(ExpiryStatus::ExpiringSoon);
+        if expired > 0 {
+            self.fail(Error::MetadataExpired(format!("{} roles expired", expired)));
+        } else if expiring_soon > 0 {
+            self.fail(Error::MetadataExpiringSoon(format!(
+                "{} roles expire within {} days",
+                expiring_soon,
+                expiry_window.num_days()
+            )));
+        }
+
+        if self.json {
+            let entries: Vec<Value> = entries.iter().map(|entry| entry.to_json()).collect();
+            self.document.insert("expiry", Value::Array(entries));
+        } else {
+            for entry in &entries {
+                println!("{}", entry);
+            }
+        }
+        Ok(())
+    }
+
+    // The general information shown when no query is requested
+    pub fn device_information(&mut self) -> Result<()> {
+        let device_id = self.storage.load_device_id()?;
+        if self.json {
+            self.document.insert("device_id", json!(device_id));
+        } else if let Some(device_id) = &device_id {
+            println!("Device ID: {}", device_id);
+        }
+        if device_id.is_none() {
+            self.fail(Error::NotProvisioned("device ID not found".to_string()));
+        }
+
+        let ecus = self.storage.load_ecus()?;
+        let mut secondaries = Vec::new();
+
+        let mut primary_versions = EcuVersions::default();
+
+        for ecu in ecus {
+            if ecu.is_primary {
+                primary_versions = self.storage.load_ecu_versions(&ecu.serial)?;
+                if self.json {
+                    let mut primary = ecu.to_json();
+                    primary["installed_versions"] = primary_versions.to_json();
+                    self.document.insert("primary", primary);
+                } else {
+                    println!("Primary ECU serial ID: {}", ecu.serial);
+                    println!("Primary ECU hardware ID: {}", ecu.hardware_id);
+                }
+            } else {
+                secondaries.push(ecu);
+            }
+        }
+
+        if self.json {
+            let mut secondaries_json = Vec::new();
+            for secondary in &secondaries {
+                let mut secondary_json = secondary.to_json();
+                secondary_json["installed_versions"] =
+                    self.storage.load_ecu_versions(&secondary.serial)?.to_json();
+                secondaries_json.push(secondary_json);
+            }
+            self.document
+                .insert("secondaries", Value::Array(secondaries_json));
+            return Ok(());
+        }
+
+        if !secondaries.is_empty() {
+            println!("Secondaries:");
+            for (index, secondary) in secondaries.iter().enumerate() {
+                println!(
+                    "{}) ID: {}, serial ID: {}",
+                    index + 1,
+                    secondary.id,
+                    secondary.serial
+                );
+                println!("   hardware ID: {}", secondary.hardware_id);
+                print!("{}", self.storage.load_ecu_versions(&secondary.serial)?);
+            }
+        }
+
+        match primary_versions.current {
+            Some(current) => {
+                println!("Current Primary ECU running version: {}", current.sha256);
+                println!(
+                    "Current Primary ECU running version filename: {}",
+                    current.name
+                );
+                println!(
+                    "Current Primary ECU running version length: {}",
+                    current.length
+                );
+            }
+            None => println!("No currently running version on Primary ECU"),
+        }
+
+        if let Some(pending) = primary_versions.pending {
+            println!("Pending Primary ECU version: {}", pending.sha256);
+            println!("Pending Primary ECU version filename: {}", pending.name);
+            println!("Pending Primary ECU version length: {}", pending.length);
+        }
+        Ok(())
+    }
+}
+
+// Stored credentials as text, they are normally PEM
+fn printable<'a>(content: &'a [u8], invalid: &'a str) -> &'a str {
+    std::str::from_utf8(content).unwrap_or(invalid)
+}
@@ -0,0 +1,315 @@
+use crate::error::{Error, Result};
+use log::{debug, warn};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde_json::{json, Value};
+use std::fmt;
+
+// Tables and columns this reader uses, see Feature::columns. This is not aktualizr's schema
+// script, so a storage created from it records no schema version: aktualizr must not take it
+// for a storage it wrote itself.
+const READER_SCHEMA_SQL: &str = "
+CREATE TABLE version(version INTEGER);
+CREATE TABLE device_info(unique_mark INTEGER PRIMARY KEY CHECK (unique_mark = 0), device_id TEXT, is_registered INTEGER NOT NULL DEFAULT 0 CHECK (is_registered IN (0,1)));
+CREATE TABLE ecus(id INTEGER PRIMARY KEY, serial TEXT UNIQUE, hardware_id TEXT NOT NULL, is_primary INTEGER NOT NULL DEFAULT 0 CHECK (is_primary IN (0,1)));
+CREATE TABLE secondary_ecus(serial TEXT PRIMARY KEY, sec_type TEXT, public_key_type TEXT, public_key TEXT, extra TEXT, manifest TEXT);
+CREATE TABLE misconfigured_ecus(serial TEXT UNIQUE, hardware_id TEXT NOT NULL, state INTEGER NOT NULL CHECK (state IN (0,1)));
+CREATE TABLE installed_versions(id INTEGER PRIMARY KEY, ecu_serial TEXT NOT NULL, sha256 TEXT NOT NULL, name TEXT NOT NULL, hashes TEXT NOT NULL, length INTEGER NOT NULL DEFAULT 0, correlation_id TEXT NOT NULL DEFAULT '', is_current INTEGER NOT NULL CHECK (is_current IN (0,1)) DEFAULT 0, is_pending INTEGER NOT NULL CHECK (is_pending IN (0,1)) DEFAULT 0, was_installed INTEGER NOT NULL CHECK (was_installed IN (0,1)) DEFAULT 0);
+CREATE TABLE primary_keys(unique_mark INTEGER PRIMARY KEY CHECK (unique_mark = 0), private TEXT, public TEXT);
+CREATE TABLE tls_creds(ca_cert BLOB, ca_cert_format TEXT, client_cert BLOB, client_cert_format TEXT, client_pkey BLOB, client_pkey_format TEXT);
+CREATE TABLE repo_types(repo INTEGER, repo_string TEXT);
+INSERT INTO repo_types(repo, repo_string) VALUES (0, 'images'), (1, 'director');
+CREATE TABLE meta_types(meta INTEGER, meta_string TEXT);
+INSERT INTO meta_types(meta, meta_string) VALUES (0, 'root'), (1, 'snapshot'), (2, 'targets'), (3, 'timestamp');
+CREATE TABLE meta(meta BLOB NOT NULL, repo INTEGER NOT NULL, meta_type INTEGER NOT NULL, version INTEGER NOT NULL, UNIQUE(repo, meta_type, version));
+CREATE TABLE target_images(targetname TEXT UNIQUE, filename TEXT UNIQUE);
+CREATE TABLE delegations(meta BLOB NOT NULL, role_name TEXT NOT NULL, UNIQUE(role_name));
+CREATE TABLE ecu_installation_results(ecu_serial TEXT NOT NULL PRIMARY KEY, success INTEGER NOT NULL DEFAULT 0, result_code TEXT NOT NULL DEFAULT '', description TEXT NOT NULL DEFAULT '');
+CREATE TABLE device_installation_result(unique_mark INTEGER PRIMARY KEY CHECK (unique_mark = 0), success INTEGER NOT NULL DEFAULT 0, result_code TEXT NOT NULL DEFAULT '', description TEXT NOT NULL DEFAULT '', raw_report TEXT NOT NULL DEFAULT '', correlation_id TEXT NOT NULL DEFAULT '');
+CREATE TABLE need_reboot(unique_mark INTEGER PRIMARY KEY CHECK (unique_mark = 0), flag INTEGER NOT NULL DEFAULT 0);
+CREATE TABLE rollback_migrations(version_from INT PRIMARY KEY, migration TEXT NOT NULL);
+CREATE TABLE report_events(id INTEGER PRIMARY KEY, json_string TEXT NOT NULL);
+CREATE TABLE device_data(data_type TEXT PRIMARY KEY, hash TEXT NOT NULL);
+CREATE TABLE ecu_report_counter(ecu_serial TEXT NOT NULL PRIMARY KEY, counter INTEGER NOT NULL DEFAULT 0);
+";
+
+/// Creates the tables this reader uses in an empty storage, with an empty version table
+pub fn create_unversioned(conn: &Connection) -> Result<()> {
+    conn.execute_batch(READER_SCHEMA_SQL)?;
+    Ok(())
+}
+
+/// What the reader can show, and the tables and columns each feature needs. aktualizr added
+/// these over several schema revisions, so their availability is checked on the storage itself
+/// rather than derived from the version number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    /// Device ID and registration, `device_info`.
+    DeviceInfo,
+    /// ECUs of the device, `ecus`.
+    Ecus,
+    /// Uptane keys of the Primary, `primary_keys`.
+    PrimaryKeys,
+    /// TLS credentials, `tls_creds`.
+    TlsCredentials,
+    /// Image and Director repo metadata, `meta`.
+    Metadata,
+    /// Secondary ECUs, `secondary_ecus`.
+    Secondaries,
+    /// Installed versions of each ECU, `installed_versions`.
+    InstalledVersions,
+    /// Delegated targets metadata, `delegations`.
+    Delegations,
+    /// Results of the last installation.
+    InstallationResults,
+}
+
+impl Feature {
+    /// Every feature, in the order they are reported.
+    pub const ALL: [Feature; 9] = [
+        Feature::DeviceInfo,
+        Feature::Ecus,
+        Feature::PrimaryKeys,
+        Feature::TlsCredentials,
+        Feature::Metadata,
+        Feature::Secondaries,
+        Feature::InstalledVersions,
+        Feature::Delegations,
+        Feature::InstallationResults,
+    ];
+
+    /// Without the required features the storage is of no use and is refused
+    pub fn is_required(&self) -> bool {
+        matches!(
+            self,
+            Feature::DeviceInfo
+                | Feature::Ecus
+                | Feature::PrimaryKeys
+                | Feature::TlsCredentials
+                | Feature::Metadata
+        )
+    }
+
+    /// Name of the feature in JSON output
+    pub fn key(&self) -> &'static str {
+        match self {
+            Feature::DeviceInfo => "device_info",
+            Feature::Ecus => "ecus",
+            Feature::PrimaryKeys => "primary_keys",
+            Feature::TlsCredentials => "tls_credentials",
+            Feature::Metadata => "metadata",
+            Feature::Secondaries => "secondaries",
+            Feature::InstalledVersions => "installed_versions",
+            Feature::Delegations => "delegations",
+            Feature::InstallationResults => "installation_results",
+        }
+    }
+
+    fn columns(&self) -> &'static [(&'static str, &'static [&'static str])] {
+        match self {
+            Feature::DeviceInfo => &[("device_info", &["device_id", "is_registered"])],
+            Feature::Ecus => &[("ecus", &["id", "serial", "hardware_id", "is_primary"])],
+            Feature::PrimaryKeys => &[("primary_keys", &["private", "public"])],
+            Feature::TlsCredentials => &[("tls_creds", &["ca_cert", "client_cert", "client_pkey"])],
+            Feature::Metadata => &[("meta", &["meta", "repo", "meta_type", "version"])],
+            Feature::Secondaries => &[(
+                "secondary_ecus",
+                &[
+                    "serial",
+                    "sec_type",
+                    "public_key_type",
+                    "public_key",
+                    "extra",
+                ],
+            )],
+            Feature::InstalledVersions => &[(
+                "installed_versions",
+                &[
+                    "ecu_serial",
+                    "sha256",
+                    "name",
+                    "hashes",
+                    "length",
+                    "correlation_id",
+                    "is_current",
+                    "is_pending",
+                    "was_installed",
+                ],
+            )],
+            Feature::Delegations => &[("delegations", &["meta", "role_name"])],
+            Feature::InstallationResults => &[
+                (
+                    "ecu_installation_results",
+                    &["ecu_serial", "success", "result_code", "description"],
+                ),
+                (
+                    "device_installation_result",
+                    &["success", "result_code", "description", "correlation_id"],
+                ),
+            ],
+        }
+    }
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Feature::DeviceInfo => "device info",
+            Feature::Ecus => "ECUs",
+            Feature::PrimaryKeys => "Primary keys",
+            Feature::TlsCredentials => "TLS credentials",
+            Feature::Metadata => "metadata",
+            Feature::Secondaries => "Secondaries",
+            Feature::InstalledVersions => "installed versions",
+            Feature::Delegations => "delegations",
+            Feature::InstallationResults => "installation results",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Schema version of a storage and which features it supports
+#[derive(Debug, Clone)]
+pub struct SchemaInfo {
+    /// None for storages that record no version, such as those written by --import-fs
+    pub version: Option<i32>,
+    unavailable: Vec<Feature>,
+}
+
+impl SchemaInfo {
+    /// Reads the version of the storage and checks which features its tables support.
+    pub fn detect(conn: &Connection) -> Result<Self> {
+        let version = Self::load_version(conn)?;
+        debug!("Storage schema version {:?}", version);
+
+        let mut unavailable = Vec::new();
+        for feature in Feature::ALL {
+            if !Self::has_columns(conn, feature)? {
+                unavailable.push(feature);
+            }
+        }
+
+        Ok(SchemaInfo {
+            version,
+            unavailable,
+        })
+    }
+
+    /// The version aktualizr records in the single row of its version table, None if the table
+    /// is empty. A database without one was not written by aktualizr, nor by --import-fs.
+    pub fn load_version(conn: &Connection) -> Result<Option<i32>> {
+        let has_table: bool = conn.query_row(
+            "SELECT count(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = 'version';",
+            [],
+            |row| row.get(0),
+        )?;
+        if !has_table {
+            return Err(Error::SchemaUnknown(
+                "the storage has no version table".to_string(),
+            ));
+        }
+
+        let version: Option<i32> = conn
+            .query_row("SELECT version FROM version LIMIT 1;", [], |row| row.get(0))
+            .optional()?;
+        match version {
+            Some(version) if version < 0 => Err(Error::SchemaUnknown(format!(
+                "invalid schema version {}",
+                version
+            ))),
+            version => Ok(version),
+        }
+    }
+
+    /// Refuses schemas lacking a required feature, and warns about the features that cannot
+    /// be shown
+    pub fn check(&self) -> Result<()> {
+        if self.unavailable.iter().any(Feature::is_required) {
+            return Err(Error::SchemaTooOld {
+                version: self.version,
+                unavailable: self.unavailable.iter().map(Feature::to_string).collect(),
+            });
+        }
+
+        if !self.unavailable.is_empty() {
+            warn!(
+                "Storage schema version {} does not support {}, these are not shown",
+                self.version_name(),
+                self.unavailable_names()
+            );
+        }
+        Ok(())
+    }
+
+    /// Whether the storage has the tables and columns of the feature.
+    pub fn supports(&self, feature: Feature) -> bool {
+        !self.unavailable.contains(&feature)
+    }
+
+    /// Features the storage lacks the tables or columns of.
+    pub fn unavailable(&self) -> &[Feature] {
+        &self.unavailable
+    }
+
+    /// Same content as the Display output.
+    pub fn to_json(&self) -> Value {
+        let features: serde_json::Map<String, Value> = Feature::ALL
+            .iter()
+            .map(|feature| (feature.key().to_string(), json!(self.supports(*feature))))
+            .collect();
+        json!({
+            "version": self.version,
+            "features": features,
+        })
+    }
+
+    fn version_name(&self) -> String {
+        match self.version {
+            Some(version) => version.to_string(),
+            None => "not recorded".to_string(),
+        }
+    }
+
+    fn unavailable_names(&self) -> String {
+        self.unavailable
+            .iter()
+            .map(Feature::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn has_columns(conn: &Connection, feature: Feature) -> Result<bool> {
+        for (table, columns) in feature.columns() {
+            let mut stmt = conn.prepare("SELECT name FROM pragma_table_info(?);")?;
+            let existing = stmt
+                .query_map(params![table], |row| row.get::<_, String>(0))?
+                .collect::<Result<Vec<String>, _>>()?;
+
+            if existing.is_empty() {
+                debug!("Table {} is missing", table);
+                return Ok(false);
+            }
+            if let Some(column) = columns
+                .iter()
+                .find(|column| !existing.iter().any(|name| name == *column))
+            {
+                debug!("Column {}.{} is missing", table, column);
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+impl fmt::Display for SchemaInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Schema version: {}", self.version_name())?;
+        for feature in Feature::ALL {
+            if self.supports(feature) {
+                writeln!(f, "   {}: available", feature)?;
+            } else {
+                writeln!(f, "   {}: unavailable", feature)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,21 +1,29 @@
 use crate::ecu_serial::EcuSerial;
 use crate::hardware_identifier::HardwareIdentifier;
 use crate::public_key::PublicKey;
+use crate::types::EcuVersions;
+use serde_json::{json, Value};
 use std::fmt;
 
+/// Secondary ECU as stored by the Primary, from `ecus` and `secondary_ecus`.
 #[derive(Debug, Clone)]
 pub struct SecondaryInfo {
+    /// Serial of the Secondary.
     pub serial: EcuSerial,
+    /// Hardware ID of the Secondary.
     pub hw_id: HardwareIdentifier,
-    #[allow(dead_code)]
+    /// Type of the Secondary, `sec_type`.
     pub kind: String,
-    #[allow(dead_code)]
+    /// Uptane public key of the Secondary.
     pub pub_key: PublicKey,
-    #[allow(dead_code)]
+    /// Secondary type specific data, stored as it is.
     pub extra: String,
+    /// Versions installed on the Secondary, none until loaded separately.
+    pub versions: EcuVersions,
 }
 
 impl SecondaryInfo {
+    /// Secondary with no installed versions loaded.
     pub fn new(
         serial: EcuSerial,
         hw_id: HardwareIdentifier,
@@ -29,16 +37,33 @@ impl SecondaryInfo {
             kind,
             pub_key,
             extra,
+            versions: EcuVersions::default(),
         }
     }
 
-    pub fn default() -> Self {
+    /// Same content as the Display output.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "serial": self.serial.to_string(),
+            "hardware_id": self.hw_id.to_string(),
+            "type": self.kind,
+            "public_key": self.pub_key.to_uptane(),
+            "public_key_id": self.pub_key.key_id(),
+            "extra": self.extra,
+            "installed_versions": self.versions.to_json(),
+        })
+    }
+}
+
+impl Default for SecondaryInfo {
+    fn default() -> Self {
         SecondaryInfo {
             serial: EcuSerial::unknown(),
             hw_id: HardwareIdentifier::unknown(),
             kind: String::new(),
             pub_key: PublicKey::default(),
             extra: String::new(),
+            versions: EcuVersions::default(),
         }
     }
 }
@@ -47,7 +72,7 @@ impl fmt::Display for SecondaryInfo {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "   serial ID: {}", self.serial)?;
         writeln!(f, "   hardware ID: {}", self.hw_id)?;
-        writeln!(f, "   no details about installed nor pending images")?;
+        write!(f, "{}", self.versions)?;
         writeln!(f, "   public key ID: {}", self.pub_key.key_id())?;
         writeln!(f, "   public key:")?;
         writeln!(f, "{}", self.pub_key)?;
@@ -1,31 +1,74 @@
-use rusqlite::{params, Connection, Error, OptionalExtension, Result};
+use rusqlite::{params, Connection, OptionalExtension};
 
 use crate::crypto::KeyType;
 use crate::ecu_serial::EcuSerial;
+use crate::error::{Error, Result};
 use crate::hardware_identifier::HardwareIdentifier;
+use crate::migrations::Migrator;
 use crate::public_key::PublicKey;
+use crate::schema::{Feature, SchemaInfo};
 use crate::secondary_info::SecondaryInfo;
+use crate::tuf_metadata::{Metadata, Root, Snapshot, Targets, Timestamp};
 use crate::tuf_repository_type::RepositoryType;
 use crate::tuf_roles::Role;
-use crate::types::Ecu;
+use crate::types::{
+    DeviceInstallationResult, Ecu, EcuInstallationResult, EcuVersions, InstalledVersion,
+};
 
 use log::{debug, error, trace};
+use serde::de::DeserializeOwned;
+use std::fs::File;
+use std::io::ErrorKind;
+use std::path::Path;
+use zeroize::Zeroizing;
 
+/// Read-only connection to an aktualizr SQLite database.
 pub struct SQLStorage {
     conn: Connection,
+    schema: SchemaInfo,
 }
 
 impl SQLStorage {
-    pub fn new(database_path: &str, allow_migrate: bool) -> Result<Self> {
-        let conn = if allow_migrate {
-            Connection::open(database_path)?
-        } else {
-            // Open the database in read-only mode
-            Connection::open_with_flags(database_path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)?
-        };
-        Ok(SQLStorage { conn })
+    /// Opens the database at `database_path`.
+    ///
+    /// The database is opened read-only. If `allow_migrate` is set, it is then handed to the
+    /// [`Migrator`], which refuses every storage for now. Storages lacking a required
+    /// feature are refused.
+    pub fn new(database_path: &Path, allow_migrate: bool) -> Result<Self> {
+        if !database_path.exists() {
+            return Err(Error::StorageMissing(database_path.to_path_buf()));
+        }
+        if let Err(e) = File::open(database_path) {
+            if e.kind() == ErrorKind::PermissionDenied {
+                return Err(Error::PermissionDenied(database_path.to_path_buf()));
+            }
+        }
+
+        // Open the database in read-only mode
+        let conn =
+            Connection::open_with_flags(database_path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        if allow_migrate {
+            Migrator::new(&conn).migrate()?;
+        }
+        let schema = SchemaInfo::detect(&conn)?;
+        schema.check()?;
+        Ok(SQLStorage { conn, schema })
+    }
+
+    /// Schema version of the storage and the features it supports.
+    pub fn schema(&self) -> &SchemaInfo {
+        &self.schema
+    }
+
+    /// Counter that changes whenever another connection commits to the database.
+    pub fn load_data_version(&self) -> Result<i64> {
+        let version = self
+            .conn
+            .query_row("PRAGMA data_version;", [], |row| row.get(0))?;
+        Ok(version)
     }
 
+    /// PEM or hex encoded Uptane public key of the Primary, `None` before provisioning.
     pub fn load_primary_public(&self) -> Result<Option<String>> {
         let mut stmt = self
             .conn
@@ -34,62 +77,70 @@ impl SQLStorage {
         match stmt.query_row([], |row| row.get(0)) {
             Ok(public_key) => Ok(Some(public_key)),
             Err(rusqlite::Error::QueryReturnedNoRows) => {
-                error!("Uptane public key not found in database");
+                debug!("Uptane public key not found in database");
                 Ok(None)
             }
             Err(e) => {
                 error!("Failed to get Uptane public key: {}", e);
-                Err(e)
+                Err(e.into())
             }
         }
     }
 
-    pub fn load_primary_private(&self) -> Result<Option<String>> {
+    /// Uptane private key of the Primary, `None` as well for storages imported from an export
+    /// without secrets. The key is wiped from memory when the returned buffer is dropped.
+    pub fn load_primary_private(&self) -> Result<Option<Zeroizing<String>>> {
         let mut stmt = self
             .conn
             .prepare("SELECT private FROM primary_keys LIMIT 1;")?;
-        match stmt.query_row([], |row| row.get(0)) {
-            Ok(private_key) => Ok(Some(private_key)),
+        match stmt.query_row([], |row| {
+            row.get::<_, Option<String>>(0)
+                .map(|key| key.map(Zeroizing::new))
+        }) {
+            Ok(private_key) => Ok(private_key),
             Err(rusqlite::Error::QueryReturnedNoRows) => {
-                error!("Uptane private key not found in database");
+                debug!("Uptane private key not found in database");
                 Ok(None)
             }
             Err(e) => {
                 error!("Failed to get Uptane private key: {}", e);
-                Err(e)
+                Err(e.into())
             }
         }
     }
 
-    // This wrapper exists so that the PublicKey object won't spill into main.rs
+    /// Uptane public key of the Primary with its detected key type.
     pub fn load_primary_key(&self) -> Result<Option<PublicKey>> {
         let pub_key_str = self.load_primary_public()?;
 
         if let Some(pub_key_str) = pub_key_str {
-            let pub_key = PublicKey::new(&pub_key_str, KeyType::Unknown); // Handle KeyType appropriately if needed
+            let pub_key = PublicKey::detect(&pub_key_str);
             Ok(Some(pub_key))
         } else {
             Ok(None)
         }
     }
 
-    pub fn load_primary_keys(&self) -> Result<Option<(PublicKey, String)>> {
+    /// Uptane public key of the Primary with its private key, `None` unless both are stored.
+    pub fn load_primary_keys(&self) -> Result<Option<(PublicKey, Zeroizing<String>)>> {
         let pub_key_str = self.load_primary_public()?;
         let priv_key_str = self.load_primary_private()?;
 
         if let (Some(pub_key_str), Some(priv_key_str)) = (pub_key_str, priv_key_str) {
-            let pub_key = PublicKey::new(&pub_key_str, KeyType::Unknown); // Handle KeyType appropriately
+            let pub_key = PublicKey::detect(&pub_key_str);
             Ok(Some((pub_key, priv_key_str)))
         } else {
             Ok(None)
         }
     }
 
+    /// Deletes the Primary's Uptane keys. Needs a storage opened for writing.
     pub fn clear_primary_keys(&self) -> Result<()> {
         self.conn.execute("DELETE FROM primary_keys;", [])?;
         Ok(())
     }
 
+    /// Device ID the device was provisioned with.
     pub fn load_device_id(&self) -> Result<Option<String>> {
         let mut stmt = self
             .conn
@@ -100,17 +151,22 @@ impl SQLStorage {
         match device_id {
             Some(id) => Ok(Some(id)),
             None => {
-                error!("Device ID key not found in database");
+                debug!("Device ID key not found in database");
                 Ok(None)
             }
         }
     }
 
+    /// Reads the TLS root CA, client certificate and client private key into the given
+    /// buffers, as stored: PEM or DER. Like the Primary private key, the client private key is
+    /// wiped from memory on drop.
+    ///
+    /// Returns `false`, leaving the buffers untouched, if no credentials are stored.
     pub fn load_tls_credentials(
         &self,
         ca: &mut Vec<u8>,
         cert: &mut Vec<u8>,
-        pkey: &mut Vec<u8>,
+        pkey: &mut Zeroizing<Vec<u8>>,
     ) -> Result<bool> {
         let mut stmt = self
             .conn
@@ -119,7 +175,7 @@ impl SQLStorage {
         match stmt.query_row([], |row| {
             let ca_cert: Vec<u8> = row.get(0)?;
             let client_cert: Vec<u8> = row.get(1)?;
-            let client_pkey: Vec<u8> = row.get(2)?;
+            let client_pkey = Zeroizing::new(row.get::<_, Vec<u8>>(2)?);
             Ok((ca_cert, client_cert, client_pkey))
         }) {
             Ok((ca_cert, client_cert, client_pkey)) => {
@@ -133,39 +189,42 @@ impl SQLStorage {
                     Ok(true)
                 }
             }
-            Err(Error::QueryReturnedNoRows) => {
+            Err(rusqlite::Error::QueryReturnedNoRows) => {
                 debug!("TLS credentials not found in database");
                 Ok(false)
             }
             Err(e) => {
                 error!("Failed to get TLS credentials: {}", e);
-                Err(e)
+                Err(e.into())
             }
         }
     }
 
+    /// Deletes the TLS credentials. Needs a storage opened for writing.
     pub fn clear_tls_creds(&self) -> Result<()> {
         self.conn.execute("DELETE FROM tls_creds;", [])?;
         Ok(())
     }
 
+    /// Whether the ECUs were registered with the backend.
     pub fn load_ecu_registered(&self) -> Result<bool> {
         let mut stmt = self
             .conn
             .prepare("SELECT is_registered FROM device_info LIMIT 1;")?;
         match stmt.query_row([], |row| row.get::<_, i32>(0)) {
             Ok(is_registered) => Ok(is_registered != 0),
-            Err(Error::QueryReturnedNoRows) => {
+            Err(rusqlite::Error::QueryReturnedNoRows) => {
                 debug!("Registration flag not found in database");
                 Ok(false)
             }
             Err(e) => {
                 error!("Failed to get registration flag: {}", e);
-                Err(e)
+                Err(e.into())
             }
         }
     }
 
+    /// Every ECU of the device, the Primary included.
     pub fn load_ecus(&self) -> Result<Vec<Ecu>> {
         let mut stmt = self
             .conn
@@ -185,17 +244,24 @@ impl SQLStorage {
         Ok(ecus)
     }
 
-    pub fn load_secondaries_info(
-        &self,
-        secondaries: &mut Vec<SecondaryInfo>,
-    ) -> Result<bool, rusqlite::Error> {
-        let mut stmt = self.conn.prepare(
+    /// Appends every Secondary with its keys and installed versions to `secondaries`.
+    ///
+    /// Returns `false` if the device has no Secondaries. On storages without the
+    /// `secondary_ecus` table only the serial and hardware ID are known.
+    pub fn load_secondaries_info(&self, secondaries: &mut Vec<SecondaryInfo>) -> Result<bool> {
+        let query = if self.schema.supports(Feature::Secondaries) {
             "SELECT serial, hardware_id, sec_type, public_key_type, public_key, extra
          FROM ecus
          LEFT JOIN secondary_ecus USING (serial)
          WHERE is_primary = 0
-         ORDER BY ecus.id;",
-        )?;
+         ORDER BY ecus.id;"
+        } else {
+            "SELECT serial, hardware_id, NULL, NULL, NULL, NULL
+         FROM ecus
+         WHERE is_primary = 0
+         ORDER BY ecus.id;"
+        };
+        let mut stmt = self.conn.prepare(query)?;
 
         let mut empty = true;
 
@@ -222,26 +288,146 @@ impl SQLStorage {
                 PublicKey::default()
             };
 
-            let sec_type = sec_type.unwrap_or_else(|| "".to_string());
-            let extra = extra.unwrap_or_else(|| "".to_string());
+            let sec_type = sec_type.unwrap_or_default();
+            let extra = extra.unwrap_or_default();
 
             Ok(SecondaryInfo::new(serial, hw_id, sec_type, pub_key, extra))
         })?;
 
         for secondary in rows {
-            secondaries.push(secondary?);
+            let mut secondary = secondary?;
+            secondary.versions = self.load_ecu_versions(&secondary.serial)?;
+            secondaries.push(secondary);
             empty = false;
         }
 
         Ok(!empty)
     }
 
+    /// Installation history of the ECU, oldest first. Empty if the schema does not support it.
+    pub fn load_installed_versions(&self, ecu_serial: &EcuSerial) -> Result<Vec<InstalledVersion>> {
+        if !self.schema.supports(Feature::InstalledVersions) {
+            return Ok(Vec::new());
+        }
+
+        let mut stmt = self.conn.prepare(
+            "SELECT ecu_serial, sha256, name, hashes, length, correlation_id, is_current, is_pending, was_installed
+         FROM installed_versions
+         WHERE ecu_serial = ?
+         ORDER BY id;",
+        )?;
+
+        let version_iter = stmt.query_map(params![ecu_serial.to_string()], |row| {
+            Ok(InstalledVersion {
+                ecu_serial: row.get(0)?,
+                sha256: row.get(1)?,
+                name: row.get(2)?,
+                hashes: row.get(3)?,
+                length: row.get::<_, i64>(4)?.max(0) as u64,
+                correlation_id: row.get(5)?,
+                is_current: row.get::<_, i32>(6)? != 0,
+                is_pending: row.get::<_, i32>(7)? != 0,
+                was_installed: row.get::<_, i32>(8)? != 0,
+            })
+        })?;
+
+        let versions = version_iter.collect::<Result<Vec<InstalledVersion>, _>>()?;
+
+        Ok(versions)
+    }
+
+    /// Current and pending version of the ECU.
+    pub fn load_ecu_versions(&self, ecu_serial: &EcuSerial) -> Result<EcuVersions> {
+        let mut versions = EcuVersions::default();
+
+        for version in self.load_installed_versions(ecu_serial)? {
+            if version.is_current {
+                versions.current = Some(version);
+            } else if version.is_pending {
+                versions.pending = Some(version);
+            }
+        }
+
+        if versions.current.is_none() && versions.pending.is_none() {
+            trace!("No installed versions found for ECU {}", ecu_serial);
+        }
+
+        Ok(versions)
+    }
+
+    /// Result of the last installation on each ECU. Empty if the schema does not support it.
+    pub fn load_ecu_installation_results(&self) -> Result<Vec<EcuInstallationResult>> {
+        if !self.schema.supports(Feature::InstallationResults) {
+            return Ok(Vec::new());
+        }
+
+        let mut stmt = self.conn.prepare(
+            "SELECT ecu_serial, success, result_code, description FROM ecu_installation_results ORDER BY ecu_serial;",
+        )?;
+
+        let result_iter = stmt.query_map([], |row| {
+            Ok(EcuInstallationResult {
+                ecu_serial: row.get(0)?,
+                success: row.get::<_, i32>(1)? != 0,
+                result_code: row.get(2)?,
+                description: row.get(3)?,
+            })
+        })?;
+
+        let results = result_iter.collect::<Result<Vec<EcuInstallationResult>, _>>()?;
+
+        Ok(results)
+    }
+
+    /// Result of the last installation on the device as a whole.
+    pub fn load_device_installation_result(&self) -> Result<Option<DeviceInstallationResult>> {
+        if !self.schema.supports(Feature::InstallationResults) {
+            return Ok(None);
+        }
+
+        let mut stmt = self.conn.prepare(
+            "SELECT success, result_code, description, correlation_id FROM device_installation_result LIMIT 1;",
+        )?;
+
+        let result = stmt
+            .query_row([], |row| {
+                Ok(DeviceInstallationResult {
+                    success: row.get::<_, i32>(0)? != 0,
+                    result_code: row.get(1)?,
+                    description: row.get(2)?,
+                    correlation_id: row.get(3)?,
+                })
+            })
+            .optional()?;
+
+        if result.is_none() {
+            debug!("Device installation result not found in database");
+        }
+        Ok(result)
+    }
+
+    /// Roles kept in the `meta` table. Delegations are stored separately, see
+    /// [`SQLStorage::load_all_delegations`].
+    pub fn stored_roles() -> [(RepositoryType, Role); 6] {
+        let image = RepositoryType::image();
+        let director = RepositoryType::director();
+        [
+            (image, Role::root()),
+            (image, Role::timestamp()),
+            (image, Role::snapshot()),
+            (image, Role::targets()),
+            (director, Role::root()),
+            (director, Role::targets()),
+        ]
+    }
+
+    /// Raw metadata of a role, the given `version` or the latest one.
     pub fn load_metadata(
         &self,
         repo: RepositoryType,
         role: Role,
         version: Option<i32>,
-    ) -> Result<Option<String>, rusqlite::Error> {
+    ) -> Result<Option<String>> {
         let repo_int = i32::from(repo);
         let role_int = role.to_int();
 
@@ -293,40 +479,176 @@ impl SQLStorage {
         }
     }
 
-    pub fn load_image_root(&self) -> Result<Option<String>, rusqlite::Error> {
+    /// All stored versions of the role, in ascending order.
+    pub fn load_metadata_versions(&self, repo: RepositoryType, role: Role) -> Result<Vec<i32>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT version FROM meta WHERE (repo=? AND meta_type=?) ORDER BY version ASC;",
+        )?;
+
+        let version_iter = stmt.query_map(params![i32::from(repo), role.to_int()], |row| {
+            row.get::<_, i32>(0)
+        })?;
+
+        let versions = version_iter.collect::<Result<Vec<i32>, _>>()?;
+
+        Ok(versions)
+    }
+
+    /// Raw metadata of the delegated role. `None` if the schema does not support delegations.
+    pub fn load_delegation(&self, role: &Role) -> Result<Option<String>> {
+        if !self.schema.supports(Feature::Delegations) {
+            return Ok(None);
+        }
+
+        let mut stmt = self
+            .conn
+            .prepare("SELECT meta FROM delegations WHERE role_name=? LIMIT 1;")?;
+
+        let blob: Option<Vec<u8>> = stmt
+            .query_row(params![role.to_string()], |row| row.get(0))
+            .optional()?;
+
+        match blob {
+            Some(blob) => {
+                let data = String::from_utf8(blob).map_err(|_e| {
+                    rusqlite::Error::InvalidColumnType(
+                        0,
+                        "meta".to_string(),
+                        rusqlite::types::Type::Text,
+                    )
+                })?;
+                Ok(Some(data))
+            }
+            None => {
+                trace!("Delegation {} not found in database", role);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Every stored delegation with its raw metadata, by role name.
+    pub fn load_all_delegations(&self) -> Result<Vec<(Role, String)>> {
+        if !self.schema.supports(Feature::Delegations) {
+            return Ok(Vec::new());
+        }
+
+        let mut stmt = self
+            .conn
+            .prepare("SELECT meta, role_name FROM delegations ORDER BY role_name;")?;
+
+        let delegation_iter = stmt.query_map([], |row| {
+            let blob: Vec<u8> = row.get(0)?;
+            let role_name: String = row.get(1)?;
+            let data = String::from_utf8(blob).map_err(|_e| {
+                rusqlite::Error::InvalidColumnType(
+                    0,
+                    "meta".to_string(),
+                    rusqlite::types::Type::Text,
+                )
+            })?;
+            Ok((Role::delegation(&role_name), data))
+        })?;
+
+        let delegations = delegation_iter.collect::<Result<Vec<(Role, String)>, _>>()?;
+
+        Ok(delegations)
+    }
+
+    /// Raw latest Image repository root metadata.
+    pub fn load_image_root(&self) -> Result<Option<String>> {
         self.load_metadata(RepositoryType::image(), Role::root(), None)
     }
-    pub fn load_image_root_with_version(
-        &self,
-        version: Option<i32>,
-    ) -> Result<Option<String>, rusqlite::Error> {
+    /// Raw Image repository root metadata of the given version, or the latest one.
+    pub fn load_image_root_with_version(&self, version: Option<i32>) -> Result<Option<String>> {
         self.load_metadata(RepositoryType::image(), Role::root(), version)
     }
 
-    pub fn load_director_root_with_version(
-        &self,
-        version: Option<i32>,
-    ) -> Result<Option<String>, rusqlite::Error> {
+    /// Raw Director root metadata of the given version, or the latest one.
+    pub fn load_director_root_with_version(&self, version: Option<i32>) -> Result<Option<String>> {
         self.load_metadata(RepositoryType::director(), Role::root(), version)
     }
 
-    pub fn load_director_root(&self) -> Result<Option<String>, rusqlite::Error> {
+    /// Raw latest Director root metadata.
+    pub fn load_director_root(&self) -> Result<Option<String>> {
         self.load_metadata(RepositoryType::director(), Role::root(), None)
     }
 
-    pub fn load_director_targets(&self) -> Result<Option<String>, rusqlite::Error> {
+    /// Raw latest Director targets metadata.
+    pub fn load_director_targets(&self) -> Result<Option<String>> {
         self.load_metadata(RepositoryType::director(), Role::targets(), None)
     }
 
-    pub fn load_image_snapshot(&self) -> Result<Option<String>, rusqlite::Error> {
+    /// Raw latest Image repository snapshot metadata.
+    pub fn load_image_snapshot(&self) -> Result<Option<String>> {
         self.load_metadata(RepositoryType::image(), Role::snapshot(), None)
     }
 
-    pub fn load_image_timestamp(&self) -> Result<Option<String>, rusqlite::Error> {
+    /// Raw latest Image repository timestamp metadata.
+    pub fn load_image_timestamp(&self) -> Result<Option<String>> {
         self.load_metadata(RepositoryType::image(), Role::timestamp(), None)
     }
 
-    pub fn load_image_targets(&self) -> Result<Option<String>, rusqlite::Error> {
+    /// Raw latest Image repository targets metadata.
+    pub fn load_image_targets(&self) -> Result<Option<String>> {
         self.load_metadata(RepositoryType::image(), Role::targets(), None)
     }
+
+    /// Typed counterpart of [`SQLStorage::load_metadata`]. Metadata that does not parse is an
+    /// [`Error::Parse`].
+    pub fn load_parsed_metadata<T: DeserializeOwned>(
+        &self,
+        repo: RepositoryType,
+        role: Role,
+        version: Option<i32>,
+    ) -> Result<Option<Metadata<T>>> {
+        match self.load_metadata(repo, role, version)? {
+            Some(raw) => Self::parse_metadata(&raw).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Image repository root metadata of the given version, or the latest one.
+    pub fn load_image_root_metadata(&self, version: Option<i32>) -> Result<Option<Metadata<Root>>> {
+        self.load_parsed_metadata(RepositoryType::image(), Role::root(), version)
+    }
+
+    /// Director root metadata of the given version, or the latest one.
+    pub fn load_director_root_metadata(
+        &self,
+        version: Option<i32>,
+    ) -> Result<Option<Metadata<Root>>> {
+        self.load_parsed_metadata(RepositoryType::director(), Role::root(), version)
+    }
+
+    /// Latest Image repository timestamp metadata.
+    pub fn load_image_timestamp_metadata(&self) -> Result<Option<Metadata<Timestamp>>> {
+        self.load_parsed_metadata(RepositoryType::image(), Role::timestamp(), None)
+    }
+
+    /// Latest Image repository snapshot metadata.
+    pub fn load_image_snapshot_metadata(&self) -> Result<Option<Metadata<Snapshot>>> {
+        self.load_parsed_metadata(RepositoryType::image(), Role::snapshot(), None)
+    }
+
+    /// Latest Image repository targets metadata.
+    pub fn load_image_targets_metadata(&self) -> Result<Option<Metadata<Targets>>> {
+        self.load_parsed_metadata(RepositoryType::image(), Role::targets(), None)
+    }
+
+    /// Latest Director targets metadata.
+    pub fn load_director_targets_metadata(&self) -> Result<Option<Metadata<Targets>>> {
+        self.load_parsed_metadata(RepositoryType::director(), Role::targets(), None)
+    }
+
+    /// Metadata of the delegated role.
+    pub fn load_delegation_metadata(&self, role: &Role) -> Result<Option<Metadata<Targets>>> {
+        match self.load_delegation(role)? {
+            Some(raw) => Self::parse_metadata(&raw).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn parse_metadata<T: DeserializeOwned>(raw: &str) -> Result<Metadata<T>> {
+        Ok(Metadata::parse(raw)?)
+    }
 }
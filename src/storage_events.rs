@@ -0,0 +1,235 @@
+use crate::crypto::Crypto;
+use crate::error::Result;
+use crate::sqlstorage::SQLStorage;
+use crate::tuf_metadata::{Metadata, SignedRole, Targets};
+use crate::tuf_repository_type::RepositoryType;
+use crate::types::InstalledVersion;
+use chrono::{DateTime, SecondsFormat, Utc};
+use log::debug;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use zeroize::Zeroizing;
+
+/// What changed in storage.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StorageEventKind {
+    /// A new version of a role was stored, delegations included.
+    MetadataStored {
+        /// Repo of the role.
+        repo: String,
+        /// Name of the role, or of the delegation.
+        role: String,
+        /// Version that was stored.
+        version: i32,
+    },
+    /// The device was registered or its registration was cleared.
+    RegistrationChanged {
+        /// Whether the device is registered now.
+        registered: bool,
+    },
+    /// The current or pending version of an ECU changed.
+    InstalledVersionChanged {
+        /// Serial of the ECU.
+        ecu_serial: String,
+        /// Name and hash of the current version, if any.
+        current: Option<String>,
+        /// Name and hash of the pending version, if any.
+        pending: Option<String>,
+    },
+    /// Stored TLS credentials were replaced by other ones.
+    TlsCredentialsRotated,
+}
+
+/// Change seen in storage, reported by `--watch`.
+#[derive(Debug, Clone)]
+pub struct StorageEvent {
+    /// When the change was seen.
+    pub time: DateTime<Utc>,
+    /// What changed.
+    pub kind: StorageEventKind,
+}
+
+impl StorageEvent {
+    /// Same content as the Display output, with the time in RFC 3339.
+    pub fn to_json(&self) -> Value {
+        let time = self.time.to_rfc3339_opts(SecondsFormat::Secs, true);
+        match &self.kind {
+            StorageEventKind::MetadataStored {
+                repo,
+                role,
+                version,
+            } => json!({
+                "time": time,
+                "event": "metadata_stored",
+                "repo": repo,
+                "role": role,
+                "version": version,
+            }),
+            StorageEventKind::RegistrationChanged { registered } => json!({
+                "time": time,
+                "event": "registration_changed",
+                "registered": registered,
+            }),
+            StorageEventKind::InstalledVersionChanged {
+                ecu_serial,
+                current,
+                pending,
+            } => json!({
+                "time": time,
+                "event": "installed_version_changed",
+                "ecu_serial": ecu_serial,
+                "current": current,
+                "pending": pending,
+            }),
+            StorageEventKind::TlsCredentialsRotated => json!({
+                "time": time,
+                "event": "tls_credentials_rotated",
+            }),
+        }
+    }
+}
+
+impl fmt::Display for StorageEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ",
+            self.time.to_rfc3339_opts(SecondsFormat::Secs, true)
+        )?;
+        match &self.kind {
+            StorageEventKind::MetadataStored {
+                repo,
+                role,
+                version,
+            } => write!(f, "{} {} v{} stored", repo, role, version),
+            StorageEventKind::RegistrationChanged { registered: true } => {
+                write!(f, "device registered")
+            }
+            StorageEventKind::RegistrationChanged { registered: false } => {
+                write!(f, "device no longer registered")
+            }
+            StorageEventKind::InstalledVersionChanged {
+                ecu_serial,
+                current,
+                pending,
+            } => write!(
+                f,
+                "ECU {} running {}, pending {}",
+                ecu_serial,
+                current.as_deref().unwrap_or("nothing"),
+                pending.as_deref().unwrap_or("nothing")
+            ),
+            StorageEventKind::TlsCredentialsRotated => write!(f, "TLS credentials rotated"),
+        }
+    }
+}
+
+/// The parts of the storage that events are reported for, compared between two reads
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StorageState {
+    registered: bool,
+    metadata: BTreeSet<(String, String, i32)>,
+    installed: BTreeMap<String, (Option<String>, Option<String>)>,
+    tls_digest: Option<String>,
+}
+
+impl StorageState {
+    /// Reads the state of the storage.
+    pub fn load(storage: &SQLStorage) -> Result<Self> {
+        let mut metadata = BTreeSet::new();
+        for (repo, role) in SQLStorage::stored_roles() {
+            for version in storage.load_metadata_versions(repo, role.clone())? {
+                metadata.insert((repo.to_string(), role.to_string(), version));
+            }
+        }
+
+        // Delegations are stored without their version, it has to be read from the metadata
+        for (role, raw) in storage.load_all_delegations()? {
+            match Metadata::<Targets>::parse(&raw) {
+                Ok(delegation) => {
+                    metadata.insert((
+                        RepositoryType::image().to_string(),
+                        role.to_string(),
+                        delegation.signed.version(),
+                    ));
+                }
+                Err(e) => debug!("Unable to read the version of delegation {}: {}", role, e),
+            }
+        }
+
+        let mut installed = BTreeMap::new();
+        for ecu in storage.load_ecus()? {
+            let versions = storage.load_ecu_versions(&ecu.serial)?;
+            installed.insert(
+                ecu.serial.to_string(),
+                (
+                    versions.current.as_ref().map(Self::describe),
+                    versions.pending.as_ref().map(Self::describe),
+                ),
+            );
+        }
+
+        let mut ca = Vec::new();
+        let mut cert = Vec::new();
+        let mut pkey = Zeroizing::default();
+        let tls_digest = if storage.load_tls_credentials(&mut ca, &mut cert, &mut pkey)? {
+            let mut creds = Zeroizing::new(ca);
+            creds.extend_from_slice(&cert);
+            creds.extend_from_slice(&pkey);
+            Some(Crypto::sha256digest_hex(&String::from_utf8_lossy(&creds)))
+        } else {
+            None
+        };
+
+        Ok(StorageState {
+            registered: storage.load_ecu_registered()?,
+            metadata,
+            installed,
+            tls_digest,
+        })
+    }
+
+    /// Events that lead from this state to the newer one
+    pub fn changes(&self, newer: &StorageState) -> Vec<StorageEvent> {
+        let time = Utc::now();
+        let mut kinds = Vec::new();
+
+        if self.registered != newer.registered {
+            kinds.push(StorageEventKind::RegistrationChanged {
+                registered: newer.registered,
+            });
+        }
+
+        for (repo, role, version) in newer.metadata.difference(&self.metadata) {
+            kinds.push(StorageEventKind::MetadataStored {
+                repo: repo.clone(),
+                role: role.clone(),
+                version: *version,
+            });
+        }
+
+        for (ecu_serial, versions) in &newer.installed {
+            if self.installed.get(ecu_serial) != Some(versions) {
+                kinds.push(StorageEventKind::InstalledVersionChanged {
+                    ecu_serial: ecu_serial.clone(),
+                    current: versions.0.clone(),
+                    pending: versions.1.clone(),
+                });
+            }
+        }
+
+        if self.tls_digest.is_some() && self.tls_digest != newer.tls_digest {
+            kinds.push(StorageEventKind::TlsCredentialsRotated);
+        }
+
+        kinds
+            .into_iter()
+            .map(|kind| StorageEvent { time, kind })
+            .collect()
+    }
+
+    fn describe(version: &InstalledVersion) -> String {
+        format!("{} ({})", version.name, version.sha256)
+    }
+}
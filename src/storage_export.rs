@@ -0,0 +1,189 @@
+use crate::archive::Archive;
+use crate::config::Config;
+use crate::error::{Error, Result};
+use crate::fs_import::{
+    FsLayout, DEVICE_ID_FILE, DIRECTOR_METADATA_DIR, IMAGE_METADATA_DIR, IS_REGISTERED_FILE,
+    PRIMARY_HARDWARE_ID_FILE, PRIMARY_SERIAL_FILE, SECONDARIES_FILE,
+};
+use crate::sqlstorage::SQLStorage;
+use crate::tuf_metadata::{Metadata, SignedRole, Targets};
+use crate::tuf_repository_type::RepositoryType;
+use crate::tuf_version::Version;
+use log::{debug, warn};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+
+// Summary of the device and its ECUs, with what the legacy storage has no file for
+const DEVICE_FILE: &str = "device.json";
+const DELEGATIONS_DIR: &str = "delegations";
+
+/// Collects the content of a storage as files, laid out as aktualizr's filesystem storage with
+/// the default configuration, see FsLayout::from_config, so that --import-fs reads it back:
+/// the device ID, registration and ECU files, every stored metadata version as
+/// `metadata/<repo>/<role>_v<version>.json`, the Primary keys and the TLS credentials. The
+/// delegations under metadata/repo/delegations and device.json are not imported. The private
+/// keys are only exported with secrets, as ecukey.der and pkey.pem.
+pub struct StorageExporter<'a> {
+    storage: &'a SQLStorage,
+    layout: FsLayout,
+    secrets: bool,
+}
+
+impl<'a> StorageExporter<'a> {
+    /// Exporter of the storage, with its private keys only if secrets is set.
+    pub fn new(storage: &'a SQLStorage, secrets: bool) -> Self {
+        StorageExporter {
+            storage,
+            layout: FsLayout::from_config(Path::new(""), &Config::default()),
+            secrets,
+        }
+    }
+
+    /// Archive of the storage in the layout `--import-fs` reads.
+    pub fn export(&self) -> Result<Archive> {
+        let mut archive = Archive::new();
+        archive.add(DEVICE_FILE, format!("{:#}\n", self.device_info()?));
+        self.add_device(&mut archive)?;
+        self.add_metadata(&mut archive)?;
+
+        if let Some(public) = self.storage.load_primary_public()? {
+            archive.add(&self.layout.public_key, public);
+        }
+        if self.secrets {
+            if let Some(private) = self.storage.load_primary_private()? {
+                archive.add_private(&self.layout.private_key, private.as_bytes());
+            }
+        }
+
+        let mut ca = Vec::new();
+        let mut cert = Vec::new();
+        let mut pkey = Zeroizing::default();
+        if self
+            .storage
+            .load_tls_credentials(&mut ca, &mut cert, &mut pkey)?
+        {
+            if !ca.is_empty() {
+                archive.add(&self.layout.ca_cert, ca);
+            }
+            if !cert.is_empty() {
+                archive.add(&self.layout.client_cert, cert);
+            }
+            if self.secrets && !pkey.is_empty() {
+                archive.add_private(&self.layout.client_pkey, &pkey);
+            }
+        }
+
+        Ok(archive)
+    }
+
+    /// Path of a metadata file relative to the metadata directory
+    pub fn metadata_path(repo: RepositoryType, role: &str, version: i32) -> Result<PathBuf> {
+        let directory = if repo == RepositoryType::director() {
+            DIRECTOR_METADATA_DIR
+        } else {
+            IMAGE_METADATA_DIR
+        };
+        Ok(Path::new(directory).join(Self::metadata_file_name(role, version)?))
+    }
+
+    /// Path of a delegated targets role relative to the metadata directory
+    pub fn delegation_path(role: &str, version: i32) -> Result<PathBuf> {
+        Ok(Path::new(IMAGE_METADATA_DIR)
+            .join(DELEGATIONS_DIR)
+            .join(Self::metadata_file_name(role, version)?))
+    }
+
+    // Delegation names come from metadata signed by whoever holds a targets key, anything
+    // that could leave the export directory is refused
+    fn metadata_file_name(role: &str, version: i32) -> Result<String> {
+        if role.is_empty() || role.contains(['/', '\\']) || role.contains("..") {
+            return Err(Error::Parse(format!(
+                "role name '{}' cannot be used as a file name",
+                role
+            )));
+        }
+        Ok(Version::from_int(version).role_file_name(role))
+    }
+
+    fn add_metadata(&self, archive: &mut Archive) -> Result<()> {
+        for (repo, role) in SQLStorage::stored_roles() {
+            for version in self.storage.load_metadata_versions(repo, role.clone())? {
+                if let Some(raw) = self
+                    .storage
+                    .load_metadata(repo, role.clone(), Some(version))?
+                {
+                    let path = Self::metadata_path(repo, &role.to_string(), version)?;
+                    archive.add(self.layout.metadata.join(path), raw);
+                }
+            }
+        }
+
+        // Only the latest version of a delegation is stored, without its version number
+        for (role, raw) in self.storage.load_all_delegations()? {
+            let version = match Metadata::<Targets>::parse(&raw) {
+                Ok(delegation) => delegation.signed.version(),
+                Err(e) => {
+                    debug!("Unable to read the version of delegation {}: {}", role, e);
+                    continue;
+                }
+            };
+            match Self::delegation_path(&role.to_string(), version) {
+                Ok(path) => archive.add(self.layout.metadata.join(path), raw),
+                Err(e) => warn!("Delegation not exported: {}", e),
+            }
+        }
+        Ok(())
+    }
+
+    // The files the legacy storage kept the device and its ECUs in: the Primary has its own,
+    // the Secondaries are listed one "<serial> <hardware ID>" line each
+    fn add_device(&self, archive: &mut Archive) -> Result<()> {
+        if let Some(device_id) = self.storage.load_device_id()? {
+            archive.add(DEVICE_ID_FILE, device_id);
+        }
+        if self.storage.load_ecu_registered()? {
+            archive.add(IS_REGISTERED_FILE, "1");
+        }
+
+        let ecus = self.storage.load_ecus()?;
+        if let Some(primary) = ecus.iter().find(|ecu| ecu.is_primary) {
+            archive.add(PRIMARY_SERIAL_FILE, primary.serial.to_string());
+            archive.add(PRIMARY_HARDWARE_ID_FILE, primary.hardware_id.to_string());
+            let secondaries: String = ecus
+                .iter()
+                .filter(|ecu| !ecu.is_primary)
+                .map(|ecu| format!("{} {}\n", ecu.serial, ecu.hardware_id))
+                .collect();
+            if !secondaries.is_empty() {
+                archive.add(SECONDARIES_FILE, secondaries);
+            }
+        }
+        Ok(())
+    }
+
+    fn device_info(&self) -> Result<Value> {
+        let mut ecus = Vec::new();
+        for ecu in self.storage.load_ecus()? {
+            let mut ecu_json = ecu.to_json();
+            ecu_json["installed_versions"] = self
+                .storage
+                .load_installed_versions(&ecu.serial)?
+                .iter()
+                .map(|version| version.to_json())
+                .collect();
+            ecus.push(ecu_json);
+        }
+
+        let mut secondaries = Vec::new();
+        self.storage.load_secondaries_info(&mut secondaries)?;
+
+        Ok(json!({
+            "device_id": self.storage.load_device_id()?,
+            "registered": self.storage.load_ecu_registered()?,
+            "schema_version": self.storage.schema().version,
+            "ecus": ecus,
+            "secondaries": secondaries.iter().map(|s| s.to_json()).collect::<Vec<_>>(),
+        }))
+    }
+}
@@ -0,0 +1,238 @@
+use crate::sqlstorage::SQLStorage;
+use inotify::{Inotify, WatchMask};
+use log::{debug, trace};
+use std::ffi::OsStr;
+use std::io::ErrorKind;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+// How often the data version is checked when inotify is not available
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// Quiet time after the last event before the storage is looked at, so that a transaction
+// in progress is not interrupted by our reads
+const SETTLE_TIME_MS: i32 = 100;
+
+const EVENT_BUFFER_SIZE: usize = 4096;
+
+/// Blocks until the storage may have changed. SQLite writes through journal and WAL files
+/// next to the database, so the whole storage directory is watched with inotify. When that
+/// is not possible, for instance because the directory does not exist yet, the watcher falls
+/// back to polling PRAGMA data_version, which changes whenever another connection commits.
+pub struct StorageWatcher {
+    database_path: PathBuf,
+    backend: Backend,
+}
+
+enum Backend {
+    Inotify(Inotify),
+    Polling {
+        storage: Option<SQLStorage>,
+        data_version: Option<i64>,
+    },
+}
+
+impl StorageWatcher {
+    /// Watcher of the storage at the path, which does not need to exist yet.
+    pub fn new(database_path: &Path) -> Self {
+        let backend = match Self::watch_directory(database_path) {
+            Ok(inotify) => {
+                debug!("Watching {} with inotify", database_path.display());
+                Backend::Inotify(inotify)
+            }
+            Err(e) => {
+                debug!(
+                    "Unable to watch {} with inotify, polling instead: {}",
+                    database_path.display(),
+                    e
+                );
+                let storage = SQLStorage::new(database_path, false).ok();
+                let data_version = storage.as_ref().and_then(|s| s.load_data_version().ok());
+                Backend::Polling {
+                    storage,
+                    data_version,
+                }
+            }
+        };
+
+        StorageWatcher {
+            database_path: database_path.to_path_buf(),
+            backend,
+        }
+    }
+
+    /// Returns true when the storage may have changed, false when the timeout ran out first
+    pub fn wait(&mut self, timeout: Option<Duration>) -> bool {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let database_path = self.database_path.clone();
+
+        match &mut self.backend {
+            Backend::Inotify(inotify) => Self::wait_inotify(inotify, &database_path, deadline),
+            Backend::Polling {
+                storage,
+                data_version,
+            } => Self::wait_polling(storage, data_version, &database_path, deadline),
+        }
+    }
+
+    fn watch_directory(database_path: &Path) -> std::io::Result<Inotify> {
+        let directory = match database_path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+
+        let inotify = Inotify::init()?;
+        inotify.watches().add(
+            directory,
+            WatchMask::CREATE
+                | WatchMask::MODIFY
+                | WatchMask::CLOSE_WRITE
+                | WatchMask::MOVED_TO
+                | WatchMask::DELETE,
+        )?;
+        Ok(inotify)
+    }
+
+    fn wait_inotify(
+        inotify: &mut Inotify,
+        database_path: &Path,
+        deadline: Option<Instant>,
+    ) -> bool {
+        let database_name = database_path.file_name().unwrap_or_default();
+        let mut buffer = [0; EVENT_BUFFER_SIZE];
+
+        loop {
+            let timeout_ms = match deadline {
+                Some(deadline) => {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    if remaining.is_zero() {
+                        return false;
+                    }
+                    remaining.as_millis().min(i32::MAX as u128) as i32
+                }
+                None => -1,
+            };
+
+            let ready = match Self::poll(inotify, timeout_ms) {
+                Ok(ready) => ready,
+                Err(e) => {
+                    debug!("Polling inotify failed: {}", e);
+                    sleep(POLL_INTERVAL);
+                    return true;
+                }
+            };
+            if !ready || !Self::read_events(inotify, &mut buffer, database_name) {
+                continue;
+            }
+
+            // Let the writer finish its transaction
+            loop {
+                match Self::poll(inotify, SETTLE_TIME_MS) {
+                    Ok(true) => {
+                        Self::read_events(inotify, &mut buffer, database_name);
+                    }
+                    _ => return true,
+                }
+            }
+        }
+    }
+
+    // Waits for events to be available, a negative timeout waits forever
+    fn poll(inotify: &Inotify, timeout_ms: i32) -> std::io::Result<bool> {
+        loop {
+            let mut fds = libc::pollfd {
+                fd: inotify.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            // SAFETY: fds is a single valid pollfd that lives for the duration of the call
+            let ready = unsafe { libc::poll(&mut fds, 1, timeout_ms) };
+            if ready >= 0 {
+                return Ok(ready > 0);
+            }
+
+            let e = std::io::Error::last_os_error();
+            if e.kind() != ErrorKind::Interrupted {
+                return Err(e);
+            }
+        }
+    }
+
+    // Drains the pending events, returns whether one of them concerns the database
+    fn read_events(inotify: &mut Inotify, buffer: &mut [u8], database_name: &OsStr) -> bool {
+        let mut changed = false;
+        loop {
+            match inotify.read_events(buffer) {
+                Ok(events) => {
+                    let mut empty = true;
+                    for event in events {
+                        empty = false;
+                        trace!("Storage event {:?} on {:?}", event.mask, event.name);
+                        if event
+                            .name
+                            .is_some_and(|name| Self::is_database_file(name, database_name))
+                        {
+                            changed = true;
+                        }
+                    }
+                    if empty {
+                        return changed;
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return changed,
+                Err(e) => {
+                    debug!("Reading inotify events failed: {}", e);
+                    return true;
+                }
+            }
+        }
+    }
+
+    // The shared memory file of WAL databases is also written by readers, so it is ignored
+    // to not wake up on our own queries
+    fn is_database_file(name: &OsStr, database_name: &OsStr) -> bool {
+        let name = name.to_string_lossy();
+        let database_name = database_name.to_string_lossy();
+        name == database_name
+            || name == format!("{}-wal", database_name)
+            || name == format!("{}-journal", database_name)
+    }
+
+    fn wait_polling(
+        storage: &mut Option<SQLStorage>,
+        data_version: &mut Option<i64>,
+        database_path: &Path,
+        deadline: Option<Instant>,
+    ) -> bool {
+        loop {
+            if let Some(deadline) = deadline {
+                if Instant::now() >= deadline {
+                    return false;
+                }
+            }
+            sleep(POLL_INTERVAL);
+
+            if storage.is_none() {
+                match SQLStorage::new(database_path, false) {
+                    Ok(opened) => {
+                        *data_version = opened.load_data_version().ok();
+                        *storage = Some(opened);
+                        return true;
+                    }
+                    Err(e) => {
+                        trace!("Storage not available yet: {}", e);
+                        continue;
+                    }
+                }
+            }
+
+            let current = storage.as_ref().and_then(|s| s.load_data_version().ok());
+            if current != *data_version {
+                *data_version = current;
+                return true;
+            }
+        }
+    }
+}
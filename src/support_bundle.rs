@@ -0,0 +1,211 @@
+use crate::archive::Archive;
+use crate::config::{Config, REDACTED};
+use crate::crypto::Crypto;
+use crate::error::Result;
+use crate::public_key::PublicKey;
+use crate::sqlstorage::SQLStorage;
+use crate::tuf_metadata::Metadata;
+use crate::tuf_repository_type::RepositoryType;
+use crate::tuf_roles::Role;
+use log::debug;
+use serde_json::{json, Value};
+use zeroize::Zeroizing;
+
+const DEVICE_FILE: &str = "device.json";
+const INSTALLATION_RESULTS_FILE: &str = "installation_results.json";
+const METADATA_FILE: &str = "metadata.json";
+const SCHEMA_FILE: &str = "schema.json";
+const CONFIG_FILE: &str = "config.json";
+const KEYS_FILE: &str = "keys.json";
+
+/// Collects what support needs to look into a device into one archive. Nothing secret goes
+/// in: private keys are redacted, public keys and certificates are replaced by their key IDs
+/// and digests, and secret configuration options are masked.
+pub struct SupportBundle<'a> {
+    storage: &'a SQLStorage,
+    config: &'a Config,
+}
+
+impl<'a> SupportBundle<'a> {
+    /// Bundle of the storage and the configuration it was opened with.
+    pub fn new(storage: &'a SQLStorage, config: &'a Config) -> Self {
+        SupportBundle { storage, config }
+    }
+
+    /// Archive of the support bundle.
+    pub fn collect(&self) -> Result<Archive> {
+        let mut archive = Archive::new();
+        for (path, content) in [
+            (DEVICE_FILE, self.device()?),
+            (INSTALLATION_RESULTS_FILE, self.installation_results()?),
+            (METADATA_FILE, self.metadata()?),
+            (SCHEMA_FILE, self.storage.schema().to_json()),
+            (CONFIG_FILE, self.config.to_redacted_json()),
+            (KEYS_FILE, self.keys()?),
+        ] {
+            archive.add(path, format!("{:#}\n", content));
+        }
+        Ok(archive)
+    }
+
+    fn device(&self) -> Result<Value> {
+        let mut ecus = Vec::new();
+        for ecu in self.storage.load_ecus()? {
+            let mut ecu_json = ecu.to_json();
+            ecu_json["installed_versions"] = self
+                .storage
+                .load_installed_versions(&ecu.serial)?
+                .iter()
+                .map(|version| version.to_json())
+                .collect();
+            ecus.push(ecu_json);
+        }
+
+        let mut secondaries = Vec::new();
+        self.storage.load_secondaries_info(&mut secondaries)?;
+        let secondaries: Vec<Value> = secondaries
+            .iter()
+            .map(|secondary| {
+                json!({
+                    "serial": secondary.serial.to_string(),
+                    "hardware_id": secondary.hw_id.to_string(),
+                    "type": secondary.kind,
+                    "public_key_type": secondary.pub_key.key_type().to_string(),
+                    "public_key_id": secondary.pub_key.key_id(),
+                    "installed_versions": secondary.versions.to_json(),
+                })
+            })
+            .collect();
+
+        Ok(json!({
+            "device_id": self.storage.load_device_id()?,
+            "registered": self.storage.load_ecu_registered()?,
+            "ecus": ecus,
+            "secondaries": secondaries,
+        }))
+    }
+
+    fn installation_results(&self) -> Result<Value> {
+        let ecus: Vec<Value> = self
+            .storage
+            .load_ecu_installation_results()?
+            .iter()
+            .map(|result| result.to_json())
+            .collect();
+        Ok(json!({
+            "device": self
+                .storage
+                .load_device_installation_result()?
+                .map(|result| result.to_json()),
+            "ecus": ecus,
+        }))
+    }
+
+    // Stored versions of every role, and what the latest one says about itself
+    fn metadata(&self) -> Result<Value> {
+        let mut summaries = Vec::new();
+        for (repo, role) in SQLStorage::stored_roles() {
+            let versions = self.storage.load_metadata_versions(repo, role.clone())?;
+            let latest = self.storage.load_metadata(repo, role.clone(), None)?;
+            summaries.push(Self::summarize(repo, &role, versions, latest.as_deref()));
+        }
+        for (role, raw) in self.storage.load_all_delegations()? {
+            summaries.push(Self::summarize(
+                RepositoryType::image(),
+                &role,
+                Vec::new(),
+                Some(&raw),
+            ));
+        }
+        Ok(Value::Array(summaries))
+    }
+
+    fn summarize(
+        repo: RepositoryType,
+        role: &Role,
+        versions: Vec<i32>,
+        raw: Option<&str>,
+    ) -> Value {
+        let mut summary = json!({
+            "repo": repo.to_string(),
+            "role": role.to_string(),
+            "stored_versions": versions,
+            "latest": null,
+        });
+        let Some(raw) = raw else {
+            return summary;
+        };
+
+        let metadata = match Metadata::<Value>::parse(raw) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                debug!("Unable to summarize {} {}: {}", repo, role, e);
+                summary["latest"] = json!({ "error": e.to_string() });
+                return summary;
+            }
+        };
+
+        let signed = &metadata.signed;
+        let mut latest = json!({
+            "version": signed["version"],
+            "expires": signed["expires"],
+            "signature_key_ids": metadata
+                .signatures
+                .iter()
+                .map(|signature| signature.keyid.as_str())
+                .collect::<Vec<_>>(),
+        });
+        if let Some(roles) = signed.get("roles") {
+            latest["roles"] = roles.clone();
+        }
+        if let Some(targets) = signed.get("targets").and_then(Value::as_object) {
+            latest["targets"] = json!(targets.keys().collect::<Vec<_>>());
+        }
+        summary["latest"] = latest;
+        summary
+    }
+
+    fn keys(&self) -> Result<Value> {
+        let primary = self.storage.load_primary_key()?.map(|key| {
+            json!({
+                "public_key_type": key.key_type().to_string(),
+                "public_key_id": key.key_id(),
+                "private_key": REDACTED,
+            })
+        });
+
+        let mut ca = Vec::new();
+        let mut cert = Vec::new();
+        let mut pkey = Zeroizing::default();
+        let tls = if self
+            .storage
+            .load_tls_credentials(&mut ca, &mut cert, &mut pkey)?
+        {
+            json!({
+                "ca_cert_sha256": Self::digest(&ca),
+                "client_cert_sha256": Self::digest(&cert),
+                "client_cert_key_id": Self::certificate_key_id(&cert),
+                "client_pkey": (!pkey.is_empty()).then_some(REDACTED),
+            })
+        } else {
+            Value::Null
+        };
+
+        Ok(json!({
+            "primary": primary,
+            "tls": tls,
+        }))
+    }
+
+    fn digest(content: &[u8]) -> Option<String> {
+        (!content.is_empty()).then(|| Crypto::sha256digest_hex(&String::from_utf8_lossy(content)))
+    }
+
+    // Key ID of the public key the client certificate carries, as the backend computes it
+    fn certificate_key_id(cert: &[u8]) -> Option<String> {
+        let cert = openssl::x509::X509::from_pem(cert).ok()?;
+        let public = cert.public_key().ok()?.public_key_to_pem().ok()?;
+        let public = String::from_utf8(public).ok()?;
+        Some(PublicKey::detect(&public).key_id())
+    }
+}
@@ -0,0 +1,223 @@
+use crate::error::Result;
+use crate::sqlstorage::SQLStorage;
+use crate::tls_info::{CertificateEncoding, TlsInspector};
+use openssl::nid::Nid;
+use openssl::pkey::{PKey, Private};
+use openssl::x509::X509;
+use serde_json::{json, Value};
+use std::fmt;
+use zeroize::Zeroizing;
+
+// Parsed credential and how it was encoded, or why it could not be parsed
+type Parsed<T> = std::result::Result<(T, CertificateEncoding), String>;
+
+/// Outcome of one consistency check of the stored TLS credentials
+#[derive(Debug, Clone)]
+pub struct TlsCheckResult {
+    /// Name of the check.
+    pub check: &'static str,
+    /// Why the check failed, None if it passed.
+    pub error: Option<String>,
+}
+
+impl TlsCheckResult {
+    fn new(check: &'static str, result: std::result::Result<(), String>) -> Self {
+        TlsCheckResult {
+            check,
+            error: result.err(),
+        }
+    }
+
+    /// Whether the check passed.
+    pub fn passed(&self) -> bool {
+        self.error.is_none()
+    }
+
+    /// Same content as the Display output.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "check": self.check,
+            "passed": self.passed(),
+            "error": self.error,
+        })
+    }
+}
+
+impl fmt::Display for TlsCheckResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.error {
+            Some(error) => write!(f, "{}: FAILED: {}", self.check, error),
+            None => write!(f, "{}: OK", self.check),
+        }
+    }
+}
+
+/// Catches the provisioning mistakes seen in the field: credentials stored in the wrong
+/// encoding, a client certificate issued for another device, and a private key that does not
+/// belong to the certificate
+pub struct TlsConsistencyChecker<'a> {
+    storage: &'a SQLStorage,
+}
+
+impl<'a> TlsConsistencyChecker<'a> {
+    /// Checker of the credentials in the storage.
+    pub fn new(storage: &'a SQLStorage) -> Self {
+        TlsConsistencyChecker { storage }
+    }
+
+    /// None when the storage holds no TLS credentials
+    pub fn check(&self) -> Result<Option<Vec<TlsCheckResult>>> {
+        let mut ca = Vec::new();
+        let mut cert = Vec::new();
+        let mut pkey = Zeroizing::new(Vec::new());
+        if !self
+            .storage
+            .load_tls_credentials(&mut ca, &mut cert, &mut pkey)?
+        {
+            return Ok(None);
+        }
+
+        let client_cert = Self::parse_certificate("client certificate", &cert);
+        let private_key = Self::parse_private_key(&pkey);
+        let device_id = self.storage.load_device_id()?;
+
+        Ok(Some(vec![
+            TlsCheckResult::new(
+                "Root CA encoding",
+                Self::check_encoding("root CA", &Self::parse_certificate("root CA", &ca)),
+            ),
+            TlsCheckResult::new(
+                "Client certificate encoding",
+                Self::check_encoding("client certificate", &client_cert),
+            ),
+            TlsCheckResult::new(
+                "Client private key encoding",
+                Self::check_encoding("client private key", &private_key),
+            ),
+            TlsCheckResult::new(
+                "Client certificate matches device ID",
+                Self::check_device_id(&client_cert, device_id.as_deref()),
+            ),
+            TlsCheckResult::new(
+                "Client private key matches certificate",
+                Self::check_key_pair(&client_cert, &private_key),
+            ),
+        ]))
+    }
+
+    // aktualizr hands the credentials to curl as PEM, DER content is still checked further but
+    // reported here
+    fn check_encoding<T>(name: &str, parsed: &Parsed<T>) -> std::result::Result<(), String> {
+        match parsed.as_ref().map_err(Clone::clone)? {
+            (_, CertificateEncoding::Pem) => Ok(()),
+            (_, CertificateEncoding::Der) => {
+                Err(format!("{} is DER encoded, aktualizr expects PEM", name))
+            }
+        }
+    }
+
+    fn parse_certificate(name: &str, content: &[u8]) -> Parsed<X509> {
+        if content.is_empty() {
+            return Err(format!("no {} in storage", name));
+        }
+        TlsInspector::parse_certificates(content)
+            .map(|(mut certs, encoding)| (certs.remove(0), encoding))
+            .map_err(|e| format!("{} cannot be parsed: {}", name, e))
+    }
+
+    fn parse_private_key(content: &[u8]) -> Parsed<PKey<Private>> {
+        if content.is_empty() {
+            return Err("no client private key in storage".to_string());
+        }
+        if content.trim_ascii_start().starts_with(b"-----BEGIN") {
+            PKey::private_key_from_pem(content)
+                .map(|key| (key, CertificateEncoding::Pem))
+                .map_err(|e| format!("client private key cannot be parsed: {}", e))
+        } else {
+            PKey::private_key_from_der(content)
+                .map(|key| (key, CertificateEncoding::Der))
+                .map_err(|e| format!("client private key is neither PEM nor DER encoded: {}", e))
+        }
+    }
+
+    // The backend identifies the device by the certificate CN, some setups put it in a SAN
+    fn check_device_id(
+        cert: &Parsed<X509>,
+        device_id: Option<&str>,
+    ) -> std::result::Result<(), String> {
+        let (cert, _) = cert
+            .as_ref()
+            .map_err(|_| "client certificate unavailable".to_string())?;
+        let device_id = device_id.ok_or_else(|| "no device ID in storage".to_string())?;
+
+        let common_name = cert
+            .subject_name()
+            .entries_by_nid(Nid::COMMONNAME)
+            .next()
+            .and_then(|entry| entry.data().as_utf8().ok())
+            .map(|name| name.to_string());
+        let alt_names: Vec<String> = cert
+            .subject_alt_names()
+            .map(|names| {
+                names
+                    .iter()
+                    .filter_map(|name| name.dnsname().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        if common_name.as_deref() == Some(device_id) || alt_names.iter().any(|n| n == device_id) {
+            return Ok(());
+        }
+
+        let common_name = match common_name {
+            Some(name) => format!("CN '{}'", name),
+            None => "no CN".to_string(),
+        };
+        let alt_names = if alt_names.is_empty() {
+            "no DNS SAN".to_string()
+        } else {
+            format!("DNS SANs '{}'", alt_names.join("', '"))
+        };
+        Err(format!(
+            "certificate has {} and {}, device ID is '{}'",
+            common_name, alt_names, device_id
+        ))
+    }
+
+    fn check_key_pair(
+        cert: &Parsed<X509>,
+        key: &Parsed<PKey<Private>>,
+    ) -> std::result::Result<(), String> {
+        let (cert, _) = cert
+            .as_ref()
+            .map_err(|_| "client certificate unavailable".to_string())?;
+        let (key, _) = key
+            .as_ref()
+            .map_err(|_| "client private key unavailable".to_string())?;
+        let public = cert
+            .public_key()
+            .map_err(|e| format!("certificate public key cannot be read: {}", e))?;
+
+        if public.id() != key.id() {
+            return Err(format!(
+                "certificate holds an {} public key, the private key is {}",
+                Self::algorithm(public.id()),
+                Self::algorithm(key.id())
+            ));
+        }
+        if !public.public_eq(key) {
+            return Err("private key does not belong to the certificate's public key".to_string());
+        }
+        Ok(())
+    }
+
+    fn algorithm(id: openssl::pkey::Id) -> &'static str {
+        match id {
+            openssl::pkey::Id::RSA => "RSA",
+            openssl::pkey::Id::EC => "EC",
+            openssl::pkey::Id::ED25519 => "Ed25519",
+            _ => "unknown",
+        }
+    }
+}
@@ -0,0 +1,395 @@
+use crate::error::{Error, Result};
+use crate::tuf_expiry::ExpiryStatus;
+use chrono::{DateTime, Duration, Utc};
+use openssl::asn1::{Asn1Time, Asn1TimeRef};
+use openssl::nid::Nid;
+use openssl::pkey::Id;
+use openssl::stack::Stack;
+use openssl::x509::store::X509StoreBuilder;
+use openssl::x509::{X509NameRef, X509StoreContext, X509};
+use serde_json::{json, Value};
+use std::fmt;
+use std::net::IpAddr;
+
+/// How a certificate is stored. aktualizr writes PEM, DER shows up in hand-made storages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CertificateEncoding {
+    /// PEM, one or more certificates.
+    Pem,
+    /// DER, a single certificate.
+    Der,
+}
+
+impl fmt::Display for CertificateEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CertificateEncoding::Pem => write!(f, "PEM"),
+            CertificateEncoding::Der => write!(f, "DER"),
+        }
+    }
+}
+
+/// What a stored X.509 certificate says about itself
+#[derive(Debug, Clone)]
+pub struct CertificateInfo {
+    /// Subject distinguished name.
+    pub subject: String,
+    /// Common name of the subject, if it has one.
+    pub common_name: Option<String>,
+    /// Issuer distinguished name.
+    pub issuer: String,
+    /// Serial number in hex.
+    pub serial: String,
+    /// DNS names, IP addresses and URIs the certificate is also valid for.
+    pub subject_alt_names: Vec<String>,
+    /// Start of the validity period, None if it cannot be read.
+    pub not_before: Option<DateTime<Utc>>,
+    /// End of the validity period, None if it cannot be read.
+    pub not_after: Option<DateTime<Utc>>,
+    /// Algorithm and size of the public key.
+    pub key_algorithm: String,
+    /// How the certificate is stored.
+    pub encoding: CertificateEncoding,
+    /// Whether the certificate expired or expires within the window.
+    pub status: ExpiryStatus,
+    now: DateTime<Utc>,
+}
+
+impl CertificateInfo {
+    fn new(
+        cert: &X509,
+        encoding: CertificateEncoding,
+        now: DateTime<Utc>,
+        window: Duration,
+    ) -> Self {
+        let not_before = Self::to_datetime(cert.not_before());
+        let not_after = Self::to_datetime(cert.not_after());
+        let status = match (not_before, not_after) {
+            (Some(not_before), _) if now < not_before => {
+                ExpiryStatus::Unknown(format!("not valid before {}", not_before.to_rfc3339()))
+            }
+            (_, Some(not_after)) if not_after <= now => ExpiryStatus::Expired,
+            (_, Some(not_after)) if not_after <= now + window => ExpiryStatus::ExpiringSoon,
+            (_, Some(_)) => ExpiryStatus::Valid,
+            (_, None) => ExpiryStatus::Unknown("invalid validity period".to_string()),
+        };
+
+        CertificateInfo {
+            subject: Self::format_name(cert.subject_name()),
+            common_name: cert
+                .subject_name()
+                .entries_by_nid(Nid::COMMONNAME)
+                .next()
+                .and_then(|entry| entry.data().as_utf8().ok())
+                .map(|name| name.to_string()),
+            issuer: Self::format_name(cert.issuer_name()),
+            serial: cert
+                .serial_number()
+                .to_bn()
+                .and_then(|serial| serial.to_hex_str().map(|hex| hex.to_string()))
+                .unwrap_or_default(),
+            subject_alt_names: Self::subject_alt_names(cert),
+            not_before,
+            not_after,
+            key_algorithm: Self::key_algorithm(cert),
+            encoding,
+            status,
+            now,
+        }
+    }
+
+    /// Same content as the Display output.
+    pub fn to_json(&self) -> Value {
+        let error = match &self.status {
+            ExpiryStatus::Unknown(error) => Some(error.clone()),
+            _ => None,
+        };
+        json!({
+            "subject": self.subject,
+            "issuer": self.issuer,
+            "serial": self.serial,
+            "subject_alt_names": self.subject_alt_names,
+            "not_before": self.not_before.map(|time| time.to_rfc3339()),
+            "not_after": self.not_after.map(|time| time.to_rfc3339()),
+            "key_algorithm": self.key_algorithm,
+            "encoding": self.encoding.to_string(),
+            "status": self.status.to_string(),
+            "error": error,
+        })
+    }
+
+    fn format_name(name: &X509NameRef) -> String {
+        name.entries()
+            .map(|entry| {
+                let field = entry.object().nid().short_name().unwrap_or("?");
+                let value = entry
+                    .data()
+                    .as_utf8()
+                    .map(|value| value.to_string())
+                    .unwrap_or_default();
+                format!("{}={}", field, value)
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn subject_alt_names(cert: &X509) -> Vec<String> {
+        let Some(names) = cert.subject_alt_names() else {
+            return Vec::new();
+        };
+        names
+            .iter()
+            .filter_map(|name| {
+                if let Some(dns) = name.dnsname() {
+                    Some(format!("DNS:{}", dns))
+                } else if let Some(email) = name.email() {
+                    Some(format!("email:{}", email))
+                } else if let Some(uri) = name.uri() {
+                    Some(format!("URI:{}", uri))
+                } else {
+                    name.ipaddress()
+                        .and_then(|ip| match ip.len() {
+                            4 => <[u8; 4]>::try_from(ip).ok().map(IpAddr::from),
+                            16 => <[u8; 16]>::try_from(ip).ok().map(IpAddr::from),
+                            _ => None,
+                        })
+                        .map(|ip| format!("IP:{}", ip))
+                }
+            })
+            .collect()
+    }
+
+    fn key_algorithm(cert: &X509) -> String {
+        let key = match cert.public_key() {
+            Ok(key) => key,
+            Err(e) => return format!("unreadable ({})", e),
+        };
+        match key.id() {
+            Id::RSA => format!("RSA {}", key.bits()),
+            Id::EC => {
+                let curve = key
+                    .ec_key()
+                    .ok()
+                    .and_then(|ec| ec.group().curve_name())
+                    .and_then(|nid| nid.short_name().ok())
+                    .unwrap_or("unknown curve");
+                format!("EC {}", curve)
+            }
+            Id::ED25519 => "Ed25519".to_string(),
+            id => format!("unknown ({})", id.as_raw()),
+        }
+    }
+
+    fn to_datetime(time: &Asn1TimeRef) -> Option<DateTime<Utc>> {
+        let epoch = Asn1Time::from_unix(0).ok()?;
+        let diff = epoch.diff(time).ok()?;
+        DateTime::from_timestamp(i64::from(diff.days) * 86400 + i64::from(diff.secs), 0)
+    }
+}
+
+impl fmt::Display for CertificateInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "   Subject: {}", self.subject)?;
+        writeln!(f, "   Issuer: {}", self.issuer)?;
+        writeln!(f, "   Serial: {}", self.serial)?;
+        if self.subject_alt_names.is_empty() {
+            writeln!(f, "   Subject alternative names: none")?;
+        } else {
+            writeln!(
+                f,
+                "   Subject alternative names: {}",
+                self.subject_alt_names.join(", ")
+            )?;
+        }
+        writeln!(f, "   Key algorithm: {}", self.key_algorithm)?;
+        writeln!(f, "   Encoding: {}", self.encoding)?;
+
+        let (Some(not_before), Some(not_after)) = (self.not_before, self.not_after) else {
+            return writeln!(f, "   Validity: UNKNOWN");
+        };
+        let days = (not_after - self.now).num_days();
+        let validity = match &self.status {
+            ExpiryStatus::Expired => format!("EXPIRED {} days ago", -days),
+            ExpiryStatus::ExpiringSoon => format!("EXPIRING in {} days", days),
+            ExpiryStatus::Unknown(error) => format!("INVALID: {}", error),
+            ExpiryStatus::Valid => format!("expires in {} days", days),
+        };
+        writeln!(
+            f,
+            "   Validity: {} to {} ({})",
+            not_before.to_rfc3339(),
+            not_after.to_rfc3339(),
+            validity
+        )
+    }
+}
+
+/// Whether the client certificate chains to the stored root CA
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChainStatus {
+    /// The client certificate chains to the root CA.
+    Verified,
+    /// The client certificate does not chain to the root CA, with the reason.
+    Failed(String),
+    /// The chain could not be checked, with the reason.
+    Unavailable(String),
+}
+
+impl fmt::Display for ChainStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChainStatus::Verified => write!(f, "verified against the stored root CA"),
+            ChainStatus::Failed(e) => write!(f, "FAILED: {}", e),
+            ChainStatus::Unavailable(e) => write!(f, "not verified, {}", e),
+        }
+    }
+}
+
+/// Stored root CA and client certificates.
+#[derive(Debug, Clone)]
+pub struct TlsInfo {
+    /// Certificates of the root CA bundle.
+    pub ca_certs: Vec<CertificateInfo>,
+    /// Client certificate, None if none is stored.
+    pub client_cert: Option<CertificateInfo>,
+    /// Whether the client certificate chains to the root CA.
+    pub chain: ChainStatus,
+}
+
+impl TlsInfo {
+    /// Certificates expired or expiring soon
+    pub fn count(&self, status: &ExpiryStatus) -> usize {
+        self.ca_certs
+            .iter()
+            .chain(self.client_cert.iter())
+            .filter(|cert| cert.status == *status)
+            .count()
+    }
+
+    /// Same content as the Display output.
+    pub fn to_json(&self) -> Value {
+        let (verified, error) = match &self.chain {
+            ChainStatus::Verified => (true, None),
+            ChainStatus::Failed(e) | ChainStatus::Unavailable(e) => (false, Some(e.clone())),
+        };
+        json!({
+            "ca_certs": self.ca_certs.iter().map(CertificateInfo::to_json).collect::<Vec<_>>(),
+            "client_cert": self.client_cert.as_ref().map(CertificateInfo::to_json),
+            "chain": { "verified": verified, "error": error },
+        })
+    }
+}
+
+impl fmt::Display for TlsInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, cert) in self.ca_certs.iter().enumerate() {
+            writeln!(f, "Root CA certificate {}:", index + 1)?;
+            write!(f, "{}", cert)?;
+        }
+        match &self.client_cert {
+            Some(cert) => {
+                writeln!(f, "Client certificate:")?;
+                write!(f, "{}", cert)?;
+            }
+            None => writeln!(f, "Client certificate: none")?,
+        }
+        writeln!(f, "Client certificate chain: {}", self.chain)
+    }
+}
+
+/// Parses the stored TLS certificates. Certificates expiring before now + window are reported
+/// as expiring soon.
+pub struct TlsInspector {
+    now: DateTime<Utc>,
+    window: Duration,
+}
+
+impl TlsInspector {
+    /// Inspector reporting certificates expiring within the window as expiring soon.
+    pub fn new(window: Duration) -> Self {
+        TlsInspector {
+            now: Utc::now(),
+            window,
+        }
+    }
+
+    /// Parses the root CA and client certificate, each PEM or DER encoded, and verifies the
+    /// chain. Empty content means no certificate is stored.
+    pub fn inspect(&self, ca: &[u8], cert: &[u8]) -> Result<TlsInfo> {
+        let (ca_certs, ca_encoding) = if ca.is_empty() {
+            (Vec::new(), CertificateEncoding::Pem)
+        } else {
+            Self::parse_certificates(ca).map_err(|e| Error::Parse(format!("root CA: {}", e)))?
+        };
+        let client = if cert.is_empty() {
+            None
+        } else {
+            let (mut certs, encoding) = Self::parse_certificates(cert)
+                .map_err(|e| Error::Parse(format!("client certificate: {}", e)))?;
+            Some((certs.remove(0), certs, encoding))
+        };
+
+        let chain = match &client {
+            None => ChainStatus::Unavailable("no client certificate".to_string()),
+            Some(_) if ca_certs.is_empty() => {
+                ChainStatus::Unavailable("no root CA certificate".to_string())
+            }
+            Some((client, intermediates, _)) => {
+                Self::verify_chain(client, intermediates, &ca_certs)
+            }
+        };
+
+        Ok(TlsInfo {
+            ca_certs: ca_certs
+                .iter()
+                .map(|cert| CertificateInfo::new(cert, ca_encoding, self.now, self.window))
+                .collect(),
+            client_cert: client.map(|(cert, _, encoding)| {
+                CertificateInfo::new(&cert, encoding, self.now, self.window)
+            }),
+            chain,
+        })
+    }
+
+    /// A PEM bundle may hold several certificates, a DER file holds a single one
+    pub fn parse_certificates(
+        content: &[u8],
+    ) -> std::result::Result<(Vec<X509>, CertificateEncoding), String> {
+        if content.trim_ascii_start().starts_with(b"-----BEGIN") {
+            let certs = X509::stack_from_pem(content).map_err(|e| e.to_string())?;
+            if certs.is_empty() {
+                return Err("no certificate in PEM data".to_string());
+            }
+            Ok((certs, CertificateEncoding::Pem))
+        } else {
+            let cert = X509::from_der(content)
+                .map_err(|e| format!("neither PEM nor DER encoded: {}", e))?;
+            Ok((vec![cert], CertificateEncoding::Der))
+        }
+    }
+
+    fn verify_chain(client: &X509, intermediates: &[X509], ca_certs: &[X509]) -> ChainStatus {
+        let verify = || -> std::result::Result<ChainStatus, openssl::error::ErrorStack> {
+            let mut store = X509StoreBuilder::new()?;
+            for ca in ca_certs {
+                store.add_cert(ca.clone())?;
+            }
+            let store = store.build();
+
+            let mut chain = Stack::new()?;
+            for intermediate in intermediates {
+                chain.push(intermediate.clone())?;
+            }
+
+            let mut context = X509StoreContext::new()?;
+            context.init(&store, client, &chain, |context| {
+                Ok(if context.verify_cert()? {
+                    ChainStatus::Verified
+                } else {
+                    ChainStatus::Failed(context.error().to_string())
+                })
+            })
+        };
+        verify().unwrap_or_else(|e| ChainStatus::Failed(e.to_string()))
+    }
+}
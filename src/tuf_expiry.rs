@@ -0,0 +1,216 @@
+use crate::error::Result;
+use crate::sqlstorage::SQLStorage;
+use crate::tuf_metadata::{Metadata, Root, SignedRole, Snapshot, Targets, Timestamp};
+use crate::tuf_repository_type::RepositoryType;
+use crate::tuf_roles::Role;
+use chrono::{DateTime, Duration, Utc};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::fmt;
+
+/// Whether a role or certificate is still valid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExpiryStatus {
+    /// Valid beyond the window.
+    Valid,
+    /// Expires within the window.
+    ExpiringSoon,
+    /// Already expired.
+    Expired,
+    /// Expiry unknown, with the reason.
+    Unknown(String),
+}
+
+impl fmt::Display for ExpiryStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status_str = match self {
+            ExpiryStatus::Valid => "valid",
+            ExpiryStatus::ExpiringSoon => "expiring soon",
+            ExpiryStatus::Expired => "expired",
+            ExpiryStatus::Unknown(_) => "unknown",
+        };
+        write!(f, "{}", status_str)
+    }
+}
+
+/// Expiry of the latest stored version of a role
+#[derive(Debug, Clone)]
+pub struct ExpiryEntry {
+    /// Repo of the role.
+    pub repo: RepositoryType,
+    /// The role, or the delegation.
+    pub role: Role,
+    /// Version of the role, None if it was not found or cannot be parsed.
+    pub version: Option<i32>,
+    /// When the role expires, if known.
+    pub expires: Option<DateTime<Utc>>,
+    /// Whether the role expired or expires within the window.
+    pub status: ExpiryStatus,
+    now: DateTime<Utc>,
+}
+
+impl ExpiryEntry {
+    /// Same content as the Display output.
+    pub fn to_json(&self) -> Value {
+        let error = match &self.status {
+            ExpiryStatus::Unknown(error) => Some(error.clone()),
+            _ => None,
+        };
+        json!({
+            "repo": self.repo.to_string(),
+            "role": self.role.to_string(),
+            "version": self.version,
+            "expires": self.expires.map(|expires| expires.to_rfc3339()),
+            "status": self.status.to_string(),
+            "error": error,
+        })
+    }
+}
+
+impl fmt::Display for ExpiryEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.repo, self.role)?;
+        if let Some(version) = self.version {
+            write!(f, " v{}", version)?;
+        }
+
+        let expires = match (&self.status, self.expires) {
+            (ExpiryStatus::Unknown(error), _) => return write!(f, ": UNKNOWN: {}", error),
+            (_, Some(expires)) => expires,
+            (_, None) => return write!(f, ": UNKNOWN"),
+        };
+
+        let days = (expires - self.now).num_days();
+        match self.status {
+            ExpiryStatus::Expired => write!(
+                f,
+                ": EXPIRED on {} ({} days ago)",
+                expires.to_rfc3339(),
+                -days
+            ),
+            ExpiryStatus::ExpiringSoon => write!(
+                f,
+                ": EXPIRING on {} (in {} days)",
+                expires.to_rfc3339(),
+                days
+            ),
+            _ => write!(
+                f,
+                ": expires on {} (in {} days)",
+                expires.to_rfc3339(),
+                days
+            ),
+        }
+    }
+}
+
+/// Checks when the stored roles expire.
+pub struct ExpiryChecker<'a> {
+    storage: &'a SQLStorage,
+    now: DateTime<Utc>,
+    window: Duration,
+}
+
+impl<'a> ExpiryChecker<'a> {
+    /// Roles expiring before now + window are reported as expiring soon
+    pub fn new(storage: &'a SQLStorage, window: Duration) -> Self {
+        Self::at(storage, Utc::now(), window)
+    }
+
+    /// Checks expiry as of the given time instead of the current one
+    pub fn at(storage: &'a SQLStorage, now: DateTime<Utc>, window: Duration) -> Self {
+        ExpiryChecker {
+            storage,
+            now,
+            window,
+        }
+    }
+
+    /// Checks the latest version of every role of both repositories, delegations included
+    pub fn check_all(&self) -> Result<Vec<ExpiryEntry>> {
+        let image = RepositoryType::image();
+        let director = RepositoryType::director();
+
+        let mut entries = vec![
+            self.check::<Root>(image, Role::root(), self.storage.load_image_root()?),
+            self.check::<Timestamp>(
+                image,
+                Role::timestamp(),
+                self.storage.load_image_timestamp()?,
+            ),
+            self.check::<Snapshot>(image, Role::snapshot(), self.storage.load_image_snapshot()?),
+            self.check::<Targets>(image, Role::targets(), self.storage.load_image_targets()?),
+        ];
+
+        for (role, metadata) in self.storage.load_all_delegations()? {
+            entries.push(self.check::<Targets>(image, role, Some(metadata)));
+        }
+
+        entries.push(self.check::<Root>(
+            director,
+            Role::root(),
+            self.storage.load_director_root()?,
+        ));
+        entries.push(self.check::<Targets>(
+            director,
+            Role::targets(),
+            self.storage.load_director_targets()?,
+        ));
+
+        Ok(entries)
+    }
+
+    fn check<T: DeserializeOwned + SignedRole>(
+        &self,
+        repo: RepositoryType,
+        role: Role,
+        metadata: Option<String>,
+    ) -> ExpiryEntry {
+        let mut entry = ExpiryEntry {
+            repo,
+            role,
+            version: None,
+            expires: None,
+            status: ExpiryStatus::Unknown("not found in storage".to_string()),
+            now: self.now,
+        };
+
+        let metadata = match metadata {
+            Some(metadata) => metadata,
+            None => return entry,
+        };
+
+        let signed = match Metadata::<T>::parse(&metadata) {
+            Ok(metadata) => metadata.signed,
+            Err(e) => {
+                entry.status = ExpiryStatus::Unknown(e.to_string());
+                return entry;
+            }
+        };
+
+        entry.version = Some(signed.version());
+
+        match DateTime::parse_from_rfc3339(signed.expires()) {
+            Ok(expires) => {
+                let expires = expires.with_timezone(&Utc);
+                entry.status = if expires <= self.now {
+                    ExpiryStatus::Expired
+                } else if expires <= self.now + self.window {
+                    ExpiryStatus::ExpiringSoon
+                } else {
+                    ExpiryStatus::Valid
+                };
+                entry.expires = Some(expires);
+            }
+            Err(e) => {
+                entry.status = ExpiryStatus::Unknown(format!(
+                    "invalid expiry date '{}': {}",
+                    signed.expires(),
+                    e
+                ));
+            }
+        }
+
+        entry
+    }
+}
@@ -0,0 +1,312 @@
+use crate::canonical_json::CanonicalJson;
+use crate::crypto::{SignatureEncoding, SignatureScheme, VerificationError};
+use crate::public_key::PublicKey;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+
+/// Fields every signed role carries.
+pub trait SignedRole {
+    /// Role type, the `_type` field.
+    fn kind(&self) -> &str;
+    /// Version of the metadata.
+    fn version(&self) -> i32;
+    /// Expiry date as an ISO 8601 timestamp.
+    fn expires(&self) -> &str;
+}
+
+/// The signed/signatures envelope of a metadata file, with the signed part typed as `T`.
+///
+/// The signed part is kept as it was stored as well, since signatures are made over it and
+/// the typed model drops fields it does not know about.
+///
+/// ```
+/// use oxidizr::tuf_metadata::{Metadata, SignedRole, Targets};
+///
+/// let raw = r#"{
+///     "signatures": [],
+///     "signed": {"_type": "Targets", "version": 2, "expires": "2030-01-01T00:00:00Z", "targets": {}}
+/// }"#;
+/// let targets = Metadata::<Targets>::parse(raw)?;
+/// assert_eq!(targets.signed.version(), 2);
+/// # Ok::<(), oxidizr::tuf_metadata::MetadataError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Metadata<T> {
+    /// Typed signed part.
+    pub signed: T,
+    /// Signatures over the canonical JSON form of the signed part.
+    pub signatures: Vec<Signature>,
+    signed_value: Value,
+}
+
+impl<T: DeserializeOwned> Metadata<T> {
+    /// Parses a metadata file as stored.
+    pub fn parse(raw: &str) -> Result<Self, MetadataError> {
+        let value: Value = serde_json::from_str(raw)
+            .map_err(|e| MetadataError(format!("metadata is not valid JSON: {}", e)))?;
+        Self::from_value(value)
+    }
+
+    /// Like [`Metadata::parse`], from an already parsed JSON document.
+    pub fn from_value(mut value: Value) -> Result<Self, MetadataError> {
+        let signed_value = value
+            .get_mut("signed")
+            .map(Value::take)
+            .ok_or_else(|| MetadataError("metadata has no signed part".to_string()))?;
+        let signatures = value
+            .get_mut("signatures")
+            .map(Value::take)
+            .ok_or_else(|| MetadataError("metadata has no signatures".to_string()))?;
+
+        let signatures: Vec<Signature> = serde_json::from_value(signatures)
+            .map_err(|e| MetadataError(format!("invalid signatures: {}", e)))?;
+        let signed: T = serde_json::from_value(signed_value.clone())
+            .map_err(|e| MetadataError(format!("invalid signed part: {}", e)))?;
+
+        Ok(Metadata {
+            signed,
+            signatures,
+            signed_value,
+        })
+    }
+}
+
+impl<T> Metadata<T> {
+    /// The signed part as stored, including fields the typed model does not know about.
+    pub fn signed_value(&self) -> &Value {
+        &self.signed_value
+    }
+
+    /// Verifies one of the signatures against the canonical JSON form of the signed part as
+    /// stored. The scheme comes from the method of the signature, or from the key type when
+    /// it has none, and the encoding is detected.
+    pub fn verify_signature(
+        &self,
+        signature: &Signature,
+        key: &PublicKey,
+    ) -> Result<(), VerificationError> {
+        let scheme = if signature.method.is_empty() {
+            SignatureScheme::for_key_type(key.key_type()).ok_or_else(|| {
+                VerificationError::InvalidKey(format!(
+                    "no signature scheme for {} keys",
+                    key.key_type()
+                ))
+            })?
+        } else {
+            signature.method.parse()?
+        };
+        let message = CanonicalJson::serialize(&self.signed_value)
+            .map_err(|e| VerificationError::InvalidMessage(e.to_string()))?;
+        key.verify(
+            scheme,
+            &signature.sig,
+            SignatureEncoding::detect(&signature.sig),
+            message.as_bytes(),
+        )
+    }
+}
+
+/// A signature of a metadata file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Signature {
+    /// ID of the key that made the signature.
+    pub keyid: String,
+    /// Signature scheme, such as `rsassa-pss-sha256` or `ed25519`. Empty if not given.
+    #[serde(default)]
+    pub method: String,
+    /// Base64 or hex encoded signature.
+    pub sig: String,
+}
+
+/// The `keyval` object of a key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyValue {
+    /// PEM or hex encoded public key.
+    pub public: String,
+}
+
+/// A key listed in root or delegating targets metadata.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Key {
+    /// Key type as written in the metadata, such as `RSA` or `ED25519`.
+    pub keytype: String,
+    /// The public key.
+    pub keyval: KeyValue,
+}
+
+impl Key {
+    /// The key as a [`PublicKey`], with its type checked against the key value.
+    pub fn to_public_key(&self) -> Result<PublicKey, Box<dyn Error>> {
+        PublicKey::from_json(&json!({
+            "keytype": self.keytype,
+            "keyval": { "public": self.keyval.public },
+        }))
+    }
+}
+
+/// Keys trusted for a role and how many of them have to sign.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoleKeys {
+    /// IDs of the trusted keys.
+    pub keyids: Vec<String>,
+    /// Number of valid signatures required.
+    pub threshold: u64,
+}
+
+/// Signed part of root metadata.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Root {
+    /// Role type, `Root`.
+    #[serde(rename = "_type")]
+    pub kind: String,
+    /// Version of the metadata.
+    pub version: i32,
+    /// Expiry date as an ISO 8601 timestamp.
+    pub expires: String,
+    /// Whether the repository uses consistent snapshots.
+    #[serde(default)]
+    pub consistent_snapshot: bool,
+    /// Keys of the top-level roles, by key ID.
+    pub keys: BTreeMap<String, Key>,
+    /// Keys and threshold of each top-level role, by role name.
+    pub roles: BTreeMap<String, RoleKeys>,
+}
+
+/// An entry of the meta section of timestamp and snapshot.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MetaFile {
+    /// Version of the referenced metadata.
+    #[serde(default)]
+    pub version: Option<i32>,
+    /// Length of the referenced metadata file.
+    #[serde(default)]
+    pub length: Option<u64>,
+    /// Hashes of the referenced metadata file, by algorithm.
+    #[serde(default)]
+    pub hashes: BTreeMap<String, String>,
+}
+
+/// Signed part of timestamp metadata.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Timestamp {
+    /// Role type, `Timestamp`.
+    #[serde(rename = "_type")]
+    pub kind: String,
+    /// Version of the metadata.
+    pub version: i32,
+    /// Expiry date as an ISO 8601 timestamp.
+    pub expires: String,
+    /// The snapshot metadata it vouches for, by file name.
+    pub meta: BTreeMap<String, MetaFile>,
+}
+
+/// Signed part of snapshot metadata.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Role type, `Snapshot`.
+    #[serde(rename = "_type")]
+    pub kind: String,
+    /// Version of the metadata.
+    pub version: i32,
+    /// Expiry date as an ISO 8601 timestamp.
+    pub expires: String,
+    /// The targets metadata it vouches for, by file name.
+    pub meta: BTreeMap<String, MetaFile>,
+}
+
+/// A target file listed in targets metadata.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Target {
+    /// Hashes of the file, by algorithm.
+    pub hashes: BTreeMap<String, String>,
+    /// Length of the file in bytes.
+    pub length: u64,
+    /// Custom data, such as the ECUs and hardware IDs the target is for.
+    #[serde(default)]
+    pub custom: Option<Value>,
+}
+
+/// A role delegated by targets metadata.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DelegatedRole {
+    /// Name of the delegated role.
+    pub name: String,
+    /// IDs of the keys trusted for the role.
+    pub keyids: Vec<String>,
+    /// Number of valid signatures required.
+    pub threshold: u64,
+    /// Target paths the role is trusted for.
+    #[serde(default)]
+    pub paths: Vec<String>,
+    /// Whether roles after this one are not searched for its paths.
+    #[serde(default)]
+    pub terminating: bool,
+}
+
+/// The delegations section of targets metadata.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Delegations {
+    /// Keys of the delegated roles, by key ID.
+    pub keys: BTreeMap<String, Key>,
+    /// The delegated roles, in search order.
+    pub roles: Vec<DelegatedRole>,
+}
+
+/// Signed part of targets metadata, used for the top-level targets of both repositories and
+/// for delegated targets roles.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Targets {
+    /// Role type, `Targets`.
+    #[serde(rename = "_type")]
+    pub kind: String,
+    /// Version of the metadata.
+    pub version: i32,
+    /// Expiry date as an ISO 8601 timestamp.
+    pub expires: String,
+    /// Target files, by name.
+    pub targets: BTreeMap<String, Target>,
+    /// Roles this one delegates to.
+    #[serde(default)]
+    pub delegations: Option<Delegations>,
+    /// Custom data, such as the correlation ID of the Director's assignment.
+    #[serde(default)]
+    pub custom: Option<Value>,
+}
+
+macro_rules! impl_signed_role {
+    ($($role:ty),*) => {
+        $(
+            impl SignedRole for $role {
+                fn kind(&self) -> &str {
+                    &self.kind
+                }
+
+                fn version(&self) -> i32 {
+                    self.version
+                }
+
+                fn expires(&self) -> &str {
+                    &self.expires
+                }
+            }
+        )*
+    };
+}
+
+impl_signed_role!(Root, Timestamp, Snapshot, Targets);
+
+/// Metadata that is not valid JSON or does not have the expected structure.
+#[derive(Debug)]
+pub struct MetadataError(String);
+
+impl fmt::Display for MetadataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Metadata Error: {}", self.0)
+    }
+}
+
+impl std::error::Error for MetadataError {}
@@ -1,36 +1,49 @@
 use std::fmt;
+use std::str::FromStr;
 
+/// One of the two Uptane repositories metadata is stored for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct RepositoryType {
     type_: Type,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+/// Repository kind. The values are the ones aktualizr stores in the `repo` column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Type {
+    /// Anything else.
     Unknown = -1,
+    /// The Image repository, holding every available target.
     Image = 0,
+    /// The Director repository, assigning targets to this device's ECUs.
     Director = 1,
 }
 
 impl RepositoryType {
+    /// Display name of the Image repository.
     pub const IMAGE: &'static str = "Image";
+    /// Display name of the Director repository.
     pub const DIRECTOR: &'static str = "Director";
 
+    /// A repository of unknown type.
     pub fn new() -> Self {
         RepositoryType {
             type_: Type::Unknown,
         }
     }
 
+    /// The Image repository.
     pub fn image() -> Self {
         RepositoryType { type_: Type::Image }
     }
 
+    /// The Director repository.
     pub fn director() -> Self {
         RepositoryType {
             type_: Type::Director,
         }
     }
 
+    /// Repository for a value of the `repo` column.
     pub fn from_int(type_val: i32) -> Self {
         let type_ = match type_val {
             0 => Type::Image,
@@ -39,8 +52,18 @@ impl RepositoryType {
         };
         RepositoryType { type_ }
     }
+}
+
+impl Default for RepositoryType {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FromStr for RepositoryType {
+    type Err = String;
 
-    pub fn from_str(repo_type: &str) -> Result<Self, String> {
+    fn from_str(repo_type: &str) -> Result<Self, Self::Err> {
         let type_ = if repo_type == Self::DIRECTOR {
             Type::Director
         } else if repo_type == Self::IMAGE {
@@ -50,19 +73,16 @@ impl RepositoryType {
         };
         Ok(RepositoryType { type_ })
     }
-
-    pub fn to_string(&self) -> String {
-        match self.type_ {
-            Type::Director => Self::DIRECTOR.to_string(),
-            Type::Image => Self::IMAGE.to_string(),
-            _ => String::new(),
-        }
-    }
 }
 
 impl fmt::Display for RepositoryType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.to_string())
+        let repo_type_str = match self.type_ {
+            Type::Director => Self::DIRECTOR,
+            Type::Image => Self::IMAGE,
+            _ => "",
+        };
+        write!(f, "{}", repo_type_str)
     }
 }
 
@@ -1,17 +1,29 @@
 use std::fmt;
 
+/// Kind of a TUF role. The values are the ones aktualizr stores in the `meta_type` column.
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum RoleEnum {
+    /// Root of trust, listing the keys of every top-level role.
     Root = 0,
+    /// Versions of the targets metadata.
     Snapshot = 1,
+    /// Targets and their hashes.
     Targets = 2,
+    /// Freshness of the snapshot metadata.
     Timestamp = 3,
+    /// A role delegated by the targets metadata.
     Delegation = 4,
+    /// Snapshot used for offline updates.
     OfflineSnapshot = 5,
+    /// Targets used for offline updates.
     OfflineUpdates = 6,
+    /// Anything else.
     InvalidRole = -1,
 }
 
+/// A TUF role, either one of the standard roles or a delegation identified by its name.
+///
+/// Roles compare and order by name.
 #[derive(Debug, Clone, Eq)]
 pub struct Role {
     role: RoleEnum,
@@ -19,43 +31,55 @@ pub struct Role {
 }
 
 impl Role {
+    /// Name of the root role.
     pub const ROOT: &'static str = "root";
+    /// Name of the snapshot role.
     pub const SNAPSHOT: &'static str = "snapshot";
+    /// Name of the targets role.
     pub const TARGETS: &'static str = "targets";
+    /// Name of the timestamp role.
     pub const TIMESTAMP: &'static str = "timestamp";
+    /// Name of the offline snapshot role.
     pub const OFFLINESNAPSHOT: &'static str = "offlinesnapshot";
+    /// Name of the offline updates role.
     pub const OFFLINEUPDATES: &'static str = "offlineupdates";
 
-    // Standard role constructors
+    /// The root role.
     pub fn root() -> Self {
         Role::new(RoleEnum::Root)
     }
 
+    /// The snapshot role.
     pub fn snapshot() -> Self {
         Role::new(RoleEnum::Snapshot)
     }
 
+    /// The targets role.
     pub fn targets() -> Self {
         Role::new(RoleEnum::Targets)
     }
 
+    /// The timestamp role.
     pub fn timestamp() -> Self {
         Role::new(RoleEnum::Timestamp)
     }
 
+    /// The offline snapshot role.
     pub fn offline_snapshot() -> Self {
         Role::new(RoleEnum::OfflineSnapshot)
     }
 
+    /// The offline updates role.
     pub fn offline_updates() -> Self {
         Role::new(RoleEnum::OfflineUpdates)
     }
 
+    /// Role standing for an unknown or invalid role.
     pub fn invalid_role() -> Self {
         Role::new(RoleEnum::InvalidRole)
     }
 
-    // Delegation role
+    /// Delegated role with the given name.
     pub fn delegation(name: &str) -> Self {
         Role {
             role: RoleEnum::Delegation,
@@ -63,7 +87,7 @@ impl Role {
         }
     }
 
-    // Create a role from RoleEnum
+    /// Standard role of the given kind. Delegations need a name, use [`Role::delegation`].
     pub fn new(role_enum: RoleEnum) -> Self {
         let name = match role_enum {
             RoleEnum::Root => Role::ROOT.to_string(),
@@ -81,7 +105,7 @@ impl Role {
         }
     }
 
-    // Return all standard roles
+    /// All standard roles.
     pub fn roles() -> Vec<Role> {
         vec![
             Role::root(),
@@ -93,7 +117,7 @@ impl Role {
         ]
     }
 
-    // Check if a role is reserved
+    /// Whether `name` is the name of a standard role, which delegations cannot use.
     pub fn is_reserved(name: &str) -> bool {
         matches!(
             name,
@@ -106,14 +130,12 @@ impl Role {
         )
     }
 
-    pub fn to_string(&self) -> String {
-        self.name.clone()
-    }
-
+    /// Value of the role kind as stored in the `meta_type` column.
     pub fn to_int(&self) -> i32 {
         self.role as i32
     }
 
+    /// Whether this is a delegated role.
     pub fn is_delegation(&self) -> bool {
         self.role == RoleEnum::Delegation
     }
@@ -127,7 +149,7 @@ impl fmt::Display for Role {
 
 impl PartialOrd for Role {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.name.cmp(&other.name))
+        Some(self.cmp(other))
     }
 }
 
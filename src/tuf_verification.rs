@@ -0,0 +1,433 @@
+use crate::crypto::{Crypto, VerificationError};
+use crate::error::Result;
+use crate::sqlstorage::SQLStorage;
+use crate::tuf_metadata::{MetaFile, Metadata, Root, SignedRole, Snapshot, Targets, Timestamp};
+use crate::tuf_repository_type::RepositoryType;
+use crate::tuf_roles::Role;
+use log::{debug, trace};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::fmt;
+
+/// Outcome of checking a single stored metadata version
+#[derive(Debug, Clone)]
+pub struct CheckResult {
+    /// Repo of the metadata.
+    pub repo: RepositoryType,
+    /// Role of the metadata, or the delegation.
+    pub role: Role,
+    /// Version of the metadata, None if it cannot be parsed.
+    pub version: Option<i32>,
+    /// Why the check failed, None if it passed.
+    pub error: Option<String>,
+}
+
+impl CheckResult {
+    fn pass(repo: RepositoryType, role: Role, version: Option<i32>) -> Self {
+        CheckResult {
+            repo,
+            role,
+            version,
+            error: None,
+        }
+    }
+
+    fn fail(repo: RepositoryType, role: Role, version: Option<i32>, error: String) -> Self {
+        CheckResult {
+            repo,
+            role,
+            version,
+            error: Some(error),
+        }
+    }
+
+    /// Whether the check passed.
+    pub fn passed(&self) -> bool {
+        self.error.is_none()
+    }
+
+    /// Same content as the Display output.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "repo": self.repo.to_string(),
+            "role": self.role.to_string(),
+            "version": self.version,
+            "passed": self.passed(),
+            "error": self.error,
+        })
+    }
+}
+
+impl fmt::Display for CheckResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.repo, self.role)?;
+        if let Some(version) = self.version {
+            write!(f, " v{}", version)?;
+        }
+        match &self.error {
+            Some(error) => write!(f, ": FAILED: {}", error),
+            None => write!(f, ": OK"),
+        }
+    }
+}
+
+/// Verifies the signatures and consistency of the stored metadata.
+pub struct MetadataVerifier<'a> {
+    storage: &'a SQLStorage,
+}
+
+impl<'a> MetadataVerifier<'a> {
+    /// Verifier of the metadata in the storage.
+    pub fn new(storage: &'a SQLStorage) -> Self {
+        MetadataVerifier { storage }
+    }
+
+    /// Walks every stored root version of the repository. Root v1 has to be signed by a
+    /// threshold of its own keys, every following version by a threshold of the keys of the
+    /// previous version and of its own keys. Each link is checked on its own so that every
+    /// version breaking the chain gets reported, not only the first one.
+    pub fn verify_root_chain(&self, repo: RepositoryType) -> Result<Vec<CheckResult>> {
+        let versions = self.storage.load_metadata_versions(repo, Role::root())?;
+        if versions.is_empty() {
+            return Ok(vec![CheckResult::fail(
+                repo,
+                Role::root(),
+                None,
+                "no root metadata stored".to_string(),
+            )]);
+        }
+
+        let mut results = Vec::new();
+        let mut previous: Option<(i32, Metadata<Root>)> = None;
+
+        for version in versions {
+            let raw = match self
+                .storage
+                .load_metadata(repo, Role::root(), Some(version))?
+            {
+                Some(raw) => raw,
+                None => continue,
+            };
+
+            let root = match Metadata::<Root>::parse(&raw) {
+                Ok(root) => root,
+                Err(e) => {
+                    results.push(CheckResult::fail(
+                        repo,
+                        Role::root(),
+                        Some(version),
+                        e.to_string(),
+                    ));
+                    continue;
+                }
+            };
+
+            match Self::check_root(&root, version, previous.as_ref()) {
+                Ok(()) => results.push(CheckResult::pass(repo, Role::root(), Some(version))),
+                Err(e) => results.push(CheckResult::fail(repo, Role::root(), Some(version), e)),
+            }
+            previous = Some((version, root));
+        }
+
+        Ok(results)
+    }
+
+    /// Checks the Image repo timestamp, snapshot and targets against the latest root and
+    /// against each other: the snapshot has to match what the timestamp declares and the
+    /// targets version has to match what the snapshot declares
+    pub fn verify_image_metadata(&self) -> Result<Vec<CheckResult>> {
+        let repo = RepositoryType::image();
+        let root = self.load_latest_root(repo)?;
+        let mut results = Vec::new();
+
+        let timestamp_raw = self.storage.load_image_timestamp()?;
+        let (result, timestamp) = Self::check_metadata::<Timestamp>(
+            repo,
+            Role::timestamp(),
+            timestamp_raw.as_deref(),
+            root.as_ref(),
+            |_, _| Ok(()),
+        );
+        results.push(result);
+
+        let snapshot_raw = self.storage.load_image_snapshot()?;
+        let (result, snapshot) = Self::check_metadata::<Snapshot>(
+            repo,
+            Role::snapshot(),
+            snapshot_raw.as_deref(),
+            root.as_ref(),
+            |snapshot, raw| match &timestamp {
+                Some(timestamp) => Self::check_meta_entry(
+                    timestamp.signed.meta.get("snapshot.json"),
+                    Role::TIMESTAMP,
+                    snapshot.version(),
+                    raw,
+                ),
+                None => Err("no timestamp to check the snapshot against".to_string()),
+            },
+        );
+        results.push(result);
+
+        let targets_raw = self.storage.load_image_targets()?;
+        let (result, _) = Self::check_metadata::<Targets>(
+            repo,
+            Role::targets(),
+            targets_raw.as_deref(),
+            root.as_ref(),
+            |targets, raw| match &snapshot {
+                Some(snapshot) => Self::check_meta_entry(
+                    snapshot.signed.meta.get("targets.json"),
+                    Role::SNAPSHOT,
+                    targets.version(),
+                    raw,
+                ),
+                None => Err("no snapshot to check the targets against".to_string()),
+            },
+        );
+        results.push(result);
+
+        Ok(results)
+    }
+
+    /// Checks that the Director targets are signed by the latest Director root
+    pub fn verify_director_metadata(&self) -> Result<Vec<CheckResult>> {
+        let repo = RepositoryType::director();
+        let root = self.load_latest_root(repo)?;
+
+        let targets_raw = self.storage.load_director_targets()?;
+        let (result, _) = Self::check_metadata::<Targets>(
+            repo,
+            Role::targets(),
+            targets_raw.as_deref(),
+            root.as_ref(),
+            |_, _| Ok(()),
+        );
+
+        Ok(vec![result])
+    }
+
+    fn load_latest_root(&self, repo: RepositoryType) -> Result<Option<Metadata<Root>>> {
+        let raw = self.storage.load_metadata(repo, Role::root(), None)?;
+        Ok(raw.and_then(|raw| match Metadata::parse(&raw) {
+            Ok(root) => Some(root),
+            Err(e) => {
+                debug!("Latest {} root cannot be parsed: {}", repo, e);
+                None
+            }
+        }))
+    }
+
+    // Parses the stored metadata and checks its type, its signatures against the root and
+    // finally the given consistency check. The parsed metadata is returned even if a check
+    // fails so the roles depending on it can still be looked at.
+    fn check_metadata<T: DeserializeOwned + SignedRole>(
+        repo: RepositoryType,
+        role: Role,
+        raw: Option<&str>,
+        root: Option<&Metadata<Root>>,
+        consistency: impl FnOnce(&T, &str) -> Result<(), String>,
+    ) -> (CheckResult, Option<Metadata<T>>) {
+        let raw = match raw {
+            Some(raw) => raw,
+            None => {
+                let error = "not found in storage".to_string();
+                return (CheckResult::fail(repo, role, None, error), None);
+            }
+        };
+
+        let metadata = match Metadata::<T>::parse(raw) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                return (CheckResult::fail(repo, role, None, e.to_string()), None);
+            }
+        };
+
+        let version = Some(metadata.signed.version());
+
+        let check = || -> Result<(), String> {
+            let kind = metadata.signed.kind();
+            if !kind.eq_ignore_ascii_case(&role.to_string()) {
+                return Err(format!("unexpected metadata type '{}'", kind));
+            }
+
+            let root = root.ok_or_else(|| "no root to check signatures against".to_string())?;
+            Self::check_signatures(&metadata, &root.signed, &role.to_string())?;
+
+            consistency(&metadata.signed, raw)
+        };
+
+        let result = match check() {
+            Ok(()) => CheckResult::pass(repo, role, version),
+            Err(e) => CheckResult::fail(repo, role, version, e),
+        };
+        (result, Some(metadata))
+    }
+
+    // Compares the metadata with the version, length and hashes that another role declares
+    // for it in its meta section
+    fn check_meta_entry(
+        entry: Option<&MetaFile>,
+        declared_by: &str,
+        version: i32,
+        raw: &str,
+    ) -> Result<(), String> {
+        let entry =
+            entry.ok_or_else(|| format!("{} does not reference this metadata", declared_by))?;
+
+        if let Some(expected) = entry.version {
+            if version != expected {
+                return Err(format!(
+                    "version {} does not match version {} declared in {}",
+                    version, expected, declared_by
+                ));
+            }
+        }
+
+        if let Some(expected) = entry.length {
+            if raw.len() as u64 != expected {
+                return Err(format!(
+                    "length {} does not match length {} declared in {}",
+                    raw.len(),
+                    expected,
+                    declared_by
+                ));
+            }
+        }
+
+        for (algorithm, expected) in &entry.hashes {
+            let actual = match algorithm.as_str() {
+                "sha256" => Crypto::sha256digest_hex(raw),
+                "sha512" => Crypto::sha512digest_hex(raw),
+                _ => {
+                    debug!("Skipping unsupported hash algorithm {}", algorithm);
+                    continue;
+                }
+            };
+            if !actual.eq_ignore_ascii_case(expected) {
+                return Err(format!(
+                    "{} hash does not match the one declared in {}",
+                    algorithm, declared_by
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn check_root(
+        root: &Metadata<Root>,
+        version: i32,
+        previous: Option<&(i32, Metadata<Root>)>,
+    ) -> Result<(), String> {
+        let kind = root.signed.kind();
+        if !kind.eq_ignore_ascii_case(Role::ROOT) {
+            return Err(format!("unexpected metadata type '{}'", kind));
+        }
+
+        if root.signed.version() != version {
+            return Err(format!(
+                "declares version {} but is stored as version {}",
+                root.signed.version(),
+                version
+            ));
+        }
+
+        match previous {
+            Some((previous_version, previous_root)) => {
+                if *previous_version != version - 1 {
+                    return Err(format!(
+                        "root v{} is missing, chain jumps from v{}",
+                        version - 1,
+                        previous_version
+                    ));
+                }
+                Self::check_signatures(root, &previous_root.signed, Role::ROOT)
+                    .map_err(|e| format!("not trusted by root v{}: {}", previous_version, e))?;
+            }
+            None if version != 1 => {
+                debug!(
+                    "Oldest stored root is v{}, cannot check its parent",
+                    version
+                );
+            }
+            None => {}
+        }
+
+        Self::check_signatures(root, &root.signed, Role::ROOT)
+            .map_err(|e| format!("not trusted by its own root keys: {}", e))
+    }
+
+    // Checks that the metadata is signed by a threshold of the keys that the given root
+    // assigns to the role. Signatures are counted per key, not per key ID: the same key
+    // listed under several IDs only counts once.
+    fn check_signatures<T>(metadata: &Metadata<T>, root: &Root, role: &str) -> Result<(), String> {
+        let role_keys = root
+            .roles
+            .get(role)
+            .ok_or_else(|| format!("root does not define the {} role", role))?;
+        if role_keys.threshold < 1 {
+            return Err(format!(
+                "invalid threshold {} for {}",
+                role_keys.threshold, role
+            ));
+        }
+
+        if metadata.signatures.is_empty() {
+            return Err("metadata has no signatures".to_string());
+        }
+
+        let mut valid_keys = HashSet::new();
+
+        for signature in &metadata.signatures {
+            let keyid = signature.keyid.as_str();
+            if !role_keys.keyids.iter().any(|id| id == keyid) {
+                trace!("Key {} is not a {} key, skipping signature", keyid, role);
+                continue;
+            }
+
+            let key = match root.keys.get(keyid).map(|key| key.to_public_key()) {
+                Some(Ok(key)) => key,
+                Some(Err(e)) => {
+                    debug!("Unable to parse key {}: {}", keyid, e);
+                    continue;
+                }
+                None => {
+                    debug!("Key {} is not listed in root", keyid);
+                    continue;
+                }
+            };
+
+            let material = match key.to_der() {
+                Some(material) => material,
+                None => {
+                    debug!("Unable to encode key {}", keyid);
+                    continue;
+                }
+            };
+
+            match metadata.verify_signature(signature, &key) {
+                Ok(()) => {
+                    if !valid_keys.insert(material) {
+                        debug!("Key {} already signed under another key ID", keyid);
+                    }
+                }
+                Err(VerificationError::InvalidMessage(e)) => {
+                    return Err(format!("unable to serialize signed part: {}", e));
+                }
+                Err(e) => debug!("Invalid signature from key {}: {}", keyid, e),
+            }
+        }
+
+        if (valid_keys.len() as u64) < role_keys.threshold {
+            return Err(format!(
+                "{} of {} required {} signatures are valid",
+                valid_keys.len(),
+                role_keys.threshold,
+                role
+            ));
+        }
+
+        Ok(())
+    }
+}
@@ -1,31 +1,43 @@
 use std::fmt;
 
+/// Version of a role, or any version to stand for the latest one.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Version {
     version: i32,
 }
 
+impl Default for Version {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Version {
     const ANY_VERSION: i32 = -1;
 
+    /// Any version, the latest one.
     pub fn new() -> Self {
         Version {
             version: Self::ANY_VERSION,
         }
     }
 
+    /// The given version.
     pub fn from_int(v: i32) -> Self {
         Version { version: v }
     }
 
+    /// The version number, -1 for any version.
     pub fn version(&self) -> i32 {
         self.version
     }
 
+    /// File name of this version of the role, `<role>_v<version>.json`.
     pub fn role_file_name(&self, role: &str) -> String {
         format!("{}_v{}.json", role, self.version)
     }
 
+    /// Whether this stands for the latest version.
     pub fn is_latest(&self) -> bool {
         self.version == Self::ANY_VERSION
     }
@@ -1,10 +1,158 @@
 use crate::ecu_serial::EcuSerial;
 use crate::hardware_identifier::HardwareIdentifier;
+use serde_json::{json, Value};
+use std::fmt;
 
+/// A row of aktualizr's ecus table.
 #[derive(Debug)]
 pub struct Ecu {
+    /// Row ID, the order the ECUs were registered in.
     pub id: i32,
+    /// Serial of the ECU.
     pub serial: EcuSerial,
+    /// Hardware ID of the ECU.
     pub hardware_id: HardwareIdentifier,
+    /// Whether this is the Primary.
     pub is_primary: bool,
 }
+
+impl Ecu {
+    /// The ECU as it appears in JSON output.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "id": self.id,
+            "serial": self.serial.to_string(),
+            "hardware_id": self.hardware_id.to_string(),
+            "is_primary": self.is_primary,
+        })
+    }
+}
+
+/// A row of aktualizr's installed_versions table.
+#[derive(Debug, Clone)]
+pub struct InstalledVersion {
+    /// Serial of the ECU the image is for.
+    pub ecu_serial: EcuSerial,
+    /// SHA-256 of the image.
+    pub sha256: String,
+    /// Target name of the image.
+    pub name: String,
+    /// Hashes of the image as JSON, by algorithm.
+    pub hashes: String,
+    /// Length of the image in bytes.
+    pub length: u64,
+    /// Correlation ID of the update that installed the image.
+    pub correlation_id: String,
+    /// Whether the image is the one running.
+    pub is_current: bool,
+    /// Whether the image is installed and waits for a reboot.
+    pub is_pending: bool,
+    /// Whether the image was ever installed.
+    pub was_installed: bool,
+}
+
+impl InstalledVersion {
+    /// The version as it appears in JSON output.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "ecu_serial": self.ecu_serial.to_string(),
+            "sha256": self.sha256,
+            "name": self.name,
+            "hashes": self.hashes,
+            "length": self.length,
+            "correlation_id": self.correlation_id,
+            "is_current": self.is_current,
+            "is_pending": self.is_pending,
+            "was_installed": self.was_installed,
+        })
+    }
+}
+
+/// The currently running and pending images of a single ECU.
+#[derive(Debug, Clone, Default)]
+pub struct EcuVersions {
+    /// The running image.
+    pub current: Option<InstalledVersion>,
+    /// The image installed but not running yet.
+    pub pending: Option<InstalledVersion>,
+}
+
+impl EcuVersions {
+    /// The versions as they appear in JSON output.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "current": self.current.as_ref().map(InstalledVersion::to_json),
+            "pending": self.pending.as_ref().map(InstalledVersion::to_json),
+        })
+    }
+}
+
+impl fmt::Display for EcuVersions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.current.is_none() && self.pending.is_none() {
+            return writeln!(f, "   no details about installed nor pending images");
+        }
+
+        if let Some(current) = &self.current {
+            writeln!(f, "   installed image hash: {}", current.sha256)?;
+            writeln!(f, "   installed image filename: {}", current.name)?;
+            writeln!(f, "   installed image length: {}", current.length)?;
+        }
+        if let Some(pending) = &self.pending {
+            writeln!(f, "   pending image hash: {}", pending.sha256)?;
+            writeln!(f, "   pending image filename: {}", pending.name)?;
+            writeln!(f, "   pending image length: {}", pending.length)?;
+        }
+        Ok(())
+    }
+}
+
+/// A row of aktualizr's ecu_installation_results table.
+#[derive(Debug, Clone)]
+pub struct EcuInstallationResult {
+    /// Serial of the ECU.
+    pub ecu_serial: EcuSerial,
+    /// Whether the installation succeeded.
+    pub success: bool,
+    /// aktualizr's result code, such as `OK` or `INSTALL_FAILED`.
+    pub result_code: String,
+    /// Details of the result.
+    pub description: String,
+}
+
+impl EcuInstallationResult {
+    /// The result as it appears in JSON output.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "ecu_serial": self.ecu_serial.to_string(),
+            "success": self.success,
+            "result_code": self.result_code,
+            "description": self.description,
+        })
+    }
+}
+
+/// Outcome of the last installation on the whole device.
+#[derive(Debug, Clone)]
+pub struct DeviceInstallationResult {
+    /// Whether the installation succeeded on every ECU.
+    pub success: bool,
+    /// aktualizr's result code, such as `OK` or `INSTALL_FAILED`.
+    pub result_code: String,
+    /// Details of the result.
+    pub description: String,
+    /// Correlation ID of the update.
+    pub correlation_id: String,
+}
+
+impl DeviceInstallationResult {
+    /// The result as it appears in JSON output.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "success": self.success,
+            "result_code": self.result_code,
+            "description": self.description,
+            "correlation_id": self.correlation_id,
+        })
+    }
+}
@@ -0,0 +1,66 @@
+use serde_json::Value;
+use std::io::{self, Write};
+use std::ops::Deref;
+use zeroize::Zeroize;
+
+/// In-memory writer for output that can hold private keys. Unlike a Vec, which leaves a copy
+/// of its content behind in the freed allocation every time it grows, it moves to a larger
+/// buffer by itself and wipes the old one first. The content is wiped on drop.
+#[derive(Debug, Default)]
+pub struct ZeroizingWriter {
+    buffer: Vec<u8>,
+}
+
+impl ZeroizingWriter {
+    /// Empty writer.
+    pub fn new() -> Self {
+        ZeroizingWriter::default()
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        let needed = self.buffer.len() + additional;
+        if needed <= self.buffer.capacity() {
+            return;
+        }
+        let mut grown = Vec::with_capacity(needed.max(self.buffer.capacity() * 2));
+        grown.extend_from_slice(&self.buffer);
+        self.buffer.zeroize();
+        self.buffer = grown;
+    }
+}
+
+impl Write for ZeroizingWriter {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.reserve(data.len());
+        self.buffer.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Deref for ZeroizingWriter {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.buffer
+    }
+}
+
+impl Drop for ZeroizingWriter {
+    fn drop(&mut self) {
+        self.buffer.zeroize();
+    }
+}
+
+/// Wipes every string of a JSON value, for values that held private keys
+pub fn wipe_json(value: &mut Value) {
+    match value {
+        Value::String(s) => s.zeroize(),
+        Value::Array(values) => values.iter_mut().for_each(wipe_json),
+        Value::Object(map) => map.values_mut().for_each(wipe_json),
+        _ => {}
+    }
+}
@@ -0,0 +1,88 @@
+use oxidizr::tuf_metadata::{Metadata, Root, Targets};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Metadata captured from aktualizr devices, see fixtures/metadata/captured/README. Unlike the
+// generated fixtures, their key IDs and signatures were made by the backend, so they check
+// this reader against it rather than against itself.
+const CAPTURED_DIR: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/tests/fixtures/metadata/captured"
+);
+
+// root.json of every captured repository, with its targets.json if one was captured
+fn captured() -> Vec<(PathBuf, Metadata<Root>, Option<Metadata<Targets>>)> {
+    let mut captured = Vec::new();
+    for capture in fs::read_dir(CAPTURED_DIR).unwrap() {
+        let capture = capture.unwrap().path();
+        if !capture.is_dir() {
+            continue;
+        }
+        for repo in ["repo", "director"] {
+            let dir = capture.join(repo);
+            let root = read(&dir.join("root.json")).map(|raw| Metadata::<Root>::parse(&raw));
+            let targets =
+                read(&dir.join("targets.json")).map(|raw| Metadata::<Targets>::parse(&raw));
+            if let Some(root) = root {
+                captured.push((dir, root.unwrap(), targets.map(Result::unwrap)));
+            }
+        }
+    }
+    assert!(!captured.is_empty(), "no metadata in {}", CAPTURED_DIR);
+    captured
+}
+
+fn read(path: &Path) -> Option<String> {
+    fs::read_to_string(path).ok()
+}
+
+#[test]
+#[ignore = "needs metadata captured from a device, see fixtures/metadata/captured/README"]
+fn captured_metadata_verifies_against_its_root_keys() {
+    for (dir, root, targets) in captured() {
+        let keys = &root.signed.keys;
+        // Signatures of keys rotated out are not checked, their keys are not listed
+        for signature in root
+            .signatures
+            .iter()
+            .filter(|s| keys.contains_key(&s.keyid))
+        {
+            let key = keys[&signature.keyid].to_public_key().unwrap();
+            assert_eq!(
+                root.verify_signature(signature, &key),
+                Ok(()),
+                "{}/root.json",
+                dir.display()
+            );
+        }
+
+        if let Some(targets) = targets {
+            let role = &root.signed.roles["targets"];
+            for signature in &targets.signatures {
+                assert!(role.keyids.contains(&signature.keyid));
+                let key = keys[&signature.keyid].to_public_key().unwrap();
+                assert_eq!(
+                    targets.verify_signature(signature, &key),
+                    Ok(()),
+                    "{}/targets.json",
+                    dir.display()
+                );
+            }
+        }
+    }
+}
+
+#[test]
+#[ignore = "needs metadata captured from a device, see fixtures/metadata/captured/README"]
+fn captured_key_ids_match_their_keys() {
+    for (dir, root, _) in captured() {
+        for (keyid, key) in &root.signed.keys {
+            assert_eq!(
+                &key.to_public_key().unwrap().key_id(),
+                keyid,
+                "{}/root.json",
+                dir.display()
+            );
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use oxidizr::schema;
+use rusqlite::{params, Connection};
+use std::path::Path;
+use std::process::{Command, Output};
+use tempfile::TempDir;
+
+// (repo, meta type) of aktualizr's meta table with the flag printing it
+const METADATA_FLAGS: [(i32, i32, &str); 4] = [
+    (1, 2, "--director-targets"),
+    (0, 1, "--image-snapshot"),
+    (0, 3, "--image-timestamp"),
+    (0, 2, "--image-targets"),
+];
+
+fn metadata(repo: i32, meta_type: i32) -> String {
+    serde_json::json!({
+        "signatures": [],
+        "signed": { "repo": repo, "meta_type": meta_type, "version": 1 },
+    })
+    .to_string()
+}
+
+// Provisioned storage with one metadata version of each role printed by METADATA_FLAGS, and
+// the configuration pointing at it
+fn storage(dir: &TempDir) -> std::path::PathBuf {
+    let path = dir.path().join("sql.db");
+    let conn = Connection::open(&path).unwrap();
+    schema::create_unversioned(&conn).unwrap();
+    conn.execute_batch(
+        "INSERT INTO device_info(unique_mark, device_id, is_registered) VALUES (0, 'device', 1);
+         INSERT INTO ecus(id, serial, hardware_id, is_primary) VALUES (0, 'primary', 'primary-hw', 1);",
+    )
+    .unwrap();
+    for (repo, meta_type, _) in METADATA_FLAGS {
+        conn.execute(
+            "INSERT INTO meta(meta, repo, meta_type, version) VALUES (?, ?, ?, 1);",
+            params![metadata(repo, meta_type).into_bytes(), repo, meta_type],
+        )
+        .unwrap();
+    }
+    config(dir, &path)
+}
+
+fn config(dir: &TempDir, database: &Path) -> std::path::PathBuf {
+    let config = dir.path().join("config.toml");
+    std::fs::write(
+        &config,
+        format!(
+            "[storage]\npath = \"{}\"\nsqldb_path = \"{}\"\n",
+            dir.path().display(),
+            database.display()
+        ),
+    )
+    .unwrap();
+    config
+}
+
+fn run(config: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_oxidizr"))
+        .arg("--config")
+        .arg(config)
+        .args(args)
+        .env("RUST_LOG", "error")
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn metadata_flags_print_only_the_metadata() {
+    let dir = TempDir::new().unwrap();
+    let config = storage(&dir);
+
+    let output = run(&config, &[]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Device ID: device"));
+
+    for (repo, meta_type, flag) in METADATA_FLAGS {
+        let output = run(&config, &[flag]);
+        assert!(output.status.success(), "{}", flag);
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            format!("{}\n", metadata(repo, meta_type)),
+            "{}",
+            flag
+        );
+    }
+
+    let output = run(&config, &["--director-targets", "--format", "json"]);
+    let document: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let targets: serde_json::Value = serde_json::from_str(&metadata(1, 2)).unwrap();
+    assert_eq!(
+        document,
+        serde_json::json!({ "director": { "targets": targets } })
+    );
+}
+
+#[test]
+fn missing_secondaries_and_delegations_fail_as_not_provisioned() {
+    let dir = TempDir::new().unwrap();
+    let config = storage(&dir);
+
+    for (args, message) in [
+        (&["--secondary-keys"][..], "no secondary info found"),
+        (
+            &["--delegation", "--delegation-role", "missing"],
+            "delegation missing not found",
+        ),
+    ] {
+        let output = run(&config, args);
+        assert_eq!(output.status.code(), Some(7), "{:?}", args);
+        assert!(output.stdout.is_empty(), "{:?}", args);
+        assert!(String::from_utf8_lossy(&output.stderr).contains(message));
+    }
+}
+
+#[test]
+fn modes_cannot_be_combined() {
+    let dir = TempDir::new().unwrap();
+    let config = storage(&dir);
+    let export = dir.path().join("export");
+    let export = export.to_str().unwrap();
+
+    for args in [
+        &["--watch", "--export", export][..],
+        &["--export", export, "--ecu-keys"],
+        &["--support-bundle", "--schema"],
+        &["--import-fs", export, "--watch"],
+        &["--watch", "--director-root"],
+        &["--watch", "--allow-migrate"],
+    ] {
+        let output = run(&config, args);
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+        assert!(String::from_utf8_lossy(&output.stderr).contains("cannot be used with"));
+    }
+    assert!(!Path::new(export).exists());
+}
+
+#[test]
+fn waiting_for_provisioning_times_out_with_exit_code_12() {
+    let dir = TempDir::new().unwrap();
+    let unprovisioned = storage(&dir);
+    // The first storage is registered but has no Director root metadata, the second one is
+    // never created
+    let missing = TempDir::new().unwrap();
+    let never_created = config(&missing, &missing.path().join("sql.db"));
+
+    for config in [&unprovisioned, &never_created] {
+        let output = run(config, &["--wait-until-provisioned", "--timeout", "1"]);
+        assert_eq!(output.status.code(), Some(12));
+        assert!(output.stdout.is_empty());
+        assert!(String::from_utf8_lossy(&output.stderr)
+            .contains("device not provisioned after 1 seconds"));
+    }
+}
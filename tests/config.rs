@@ -0,0 +1,147 @@
+use oxidizr::config::{Config, REDACTED};
+use std::fs;
+use tempfile::TempDir;
+
+fn write(dir: &TempDir, name: &str, content: &str) {
+    fs::write(dir.path().join(name), content).unwrap();
+}
+
+#[test]
+fn parses_toml_syntax() {
+    let dir = TempDir::new().unwrap();
+    write(
+        &dir,
+        "10-storage.toml",
+        r#"
+# Comments may follow section headers and values
+[storage] # where aktualizr keeps its state
+path = "/var/lib/sota" # absolute
+sqldb_path = 'C:\sota\sql.db'
+
+[uptane]
+polling_sec = 10
+force_install_completion = true
+
+[provision.extra]
+tags = ["a", "b"]
+"#,
+    );
+
+    let config = Config::load(&[dir.path()]).unwrap();
+    assert_eq!(config.get("storage.path"), Some("/var/lib/sota"));
+    assert_eq!(config.get("storage.sqldb_path"), Some(r"C:\sota\sql.db"));
+    assert_eq!(config.get("uptane.polling_sec"), Some("10"));
+    assert_eq!(config.get("uptane.force_install_completion"), Some("true"));
+    assert_eq!(config.get("provision.extra.tags"), Some(r#"["a", "b"]"#));
+    assert_eq!(config.source("storage.path").unwrap().line, 4);
+}
+
+#[test]
+fn later_fragments_override_earlier_ones() {
+    let dir = TempDir::new().unwrap();
+    write(&dir, "10-a.toml", "[storage]\npath = \"/a\"\n");
+    write(&dir, "20-b.toml", "[storage]\npath = \"/b\"\n");
+
+    let config = Config::load(&[dir.path()]).unwrap();
+    assert_eq!(config.get("storage.path"), Some("/b"));
+    assert_eq!(config.get("storage.sqldb_path"), Some("sql.db"));
+}
+
+#[test]
+fn invalid_toml_is_an_error() {
+    let dir = TempDir::new().unwrap();
+    write(&dir, "10-bad.toml", "[storage\npath = \"/a\"\n");
+
+    let error = Config::load(&[dir.path()]).unwrap_err();
+    assert!(error.to_string().contains("10-bad.toml"));
+}
+
+#[test]
+fn missing_explicit_path_is_an_error() {
+    let dir = TempDir::new().unwrap();
+    let missing = dir.path().join("missing.toml");
+
+    let error = Config::load(&[&missing]).unwrap_err();
+    assert!(error.to_string().contains("does not exist"));
+}
+
+#[test]
+fn printed_config_reads_back_the_same() {
+    let dir = TempDir::new().unwrap();
+    write(
+        &dir,
+        "10-values.toml",
+        r#"
+top = "level"
+
+[storage]
+path = "/var/lib/\"sota\"\\data"
+sqldb_path = "line\nbreak\ttab\u0001"
+
+[uptane]
+polling_sec = 10
+force_install_completion = true
+endpoints = ["https://a", "https://b"]
+extra = { "key with spaces" = "x", n = 1.5 }
+
+["odd section"]
+value = 'literal'
+"#,
+    );
+    let config = Config::load(&[dir.path()]).unwrap();
+
+    let printed = TempDir::new().unwrap();
+    write(&printed, "printed.toml", &config.to_string());
+    let reread = Config::load(&[printed.path()]).unwrap();
+
+    for key in [
+        "top",
+        "storage.path",
+        "storage.sqldb_path",
+        "storage.type",
+        "uptane.polling_sec",
+        "uptane.force_install_completion",
+        "uptane.endpoints",
+        "uptane.extra.key with spaces",
+        "uptane.extra.n",
+        "odd section.value",
+        "provision.expiry_days",
+        "pacman.fake_need_reboot",
+    ] {
+        assert_eq!(reread.get(key), config.get(key), "{}", key);
+    }
+    assert!(!reread.source("uptane.polling_sec").unwrap().is_string);
+    assert!(!reread.source("pacman.fake_need_reboot").unwrap().is_string);
+    assert!(reread.source("storage.path").unwrap().is_string);
+}
+
+#[test]
+fn redacts_secret_options() {
+    let dir = TempDir::new().unwrap();
+    write(
+        &dir,
+        "10-secrets.toml",
+        "[provision]\np12_password = \"hunter2\"\nserver = \"https://example.com\"\n\n\
+         [p11]\npass = \"1234\"\nmodule = \"/usr/lib/softhsm.so\"\n\n\
+         [custom]\napi_token = \"t\"\nclient-secret = \"s\"\npin = \"0000\"\nmapping = \"m\"\n",
+    );
+    let config = Config::load(&[dir.path()]).unwrap();
+    let redacted = config.to_redacted_json();
+
+    for (section, option) in [
+        ("provision", "p12_password"),
+        ("p11", "pass"),
+        ("custom", "api_token"),
+        ("custom", "client-secret"),
+        ("custom", "pin"),
+    ] {
+        assert_eq!(redacted[section][option]["value"], REDACTED, "{}", option);
+    }
+    assert_eq!(
+        redacted["provision"]["server"]["value"],
+        "https://example.com"
+    );
+    assert_eq!(redacted["p11"]["module"]["value"], "/usr/lib/softhsm.so");
+    assert_eq!(redacted["custom"]["mapping"]["value"], "m");
+    assert!(!redacted.to_string().contains("hunter2"));
+}
@@ -0,0 +1,140 @@
+#!/usr/bin/env python3
+# Generates the metadata fixtures of tests/signature_verification.rs with fresh keys.
+#
+# The files are written the way aktualizr stores metadata it received: RSA-PSS signed image
+# repository, Ed25519 signed Director, base64 signatures with aktualizr's method names, pretty
+# printed with the keys of signed in server order rather than sorted. They are not copies of
+# metadata from an aktualizr device or backend.
+#
+# Needs the cryptography package. Usage: generate.py [OUTPUT_DIR]
+
+import base64
+import hashlib
+import json
+import os
+import sys
+
+from cryptography.hazmat.primitives import hashes, serialization
+from cryptography.hazmat.primitives.asymmetric import ed25519, padding, rsa
+
+EXPIRES = "2031-10-18T08:00:00Z"
+CREATED = "2026-10-18T08:00:00Z"
+FIRMWARE = b"firmware-1\n"
+
+
+def canonical(value):
+    return json.dumps(value, sort_keys=True, separators=(",", ":"), ensure_ascii=False)
+
+
+class Key:
+    def __init__(self, keytype):
+        self.keytype = keytype
+        if keytype == "RSA":
+            self.private = rsa.generate_private_key(65537, 2048)
+            public = self.private.public_key().public_bytes(
+                serialization.Encoding.PEM,
+                serialization.PublicFormat.SubjectPublicKeyInfo,
+            )
+            self.public = public.decode()
+        else:
+            self.private = ed25519.Ed25519PrivateKey.generate()
+            public = self.private.public_key().public_bytes(
+                serialization.Encoding.Raw, serialization.PublicFormat.Raw
+            )
+            self.public = public.hex()
+        self.uptane = {"keytype": keytype, "keyval": {"public": self.public}}
+        self.keyid = hashlib.sha256(canonical(self.uptane).encode()).hexdigest()
+
+    def sign(self, message):
+        if self.keytype == "RSA":
+            pss = padding.PSS(mgf=padding.MGF1(hashes.SHA256()), salt_length=32)
+            signature = self.private.sign(message, pss, hashes.SHA256())
+            method = "rsassa-pss"
+        else:
+            signature = self.private.sign(message)
+            method = "ed25519"
+        return {
+            "keyid": self.keyid,
+            "method": method,
+            "sig": base64.b64encode(signature).decode(),
+        }
+
+
+def signed_by(key, signed):
+    return {"signatures": [key.sign(canonical(signed).encode())], "signed": signed}
+
+
+def root(key, roles):
+    return {
+        "_type": "Root",
+        "expires": EXPIRES,
+        "version": 1,
+        "consistent_snapshot": False,
+        "keys": {key.keyid: key.uptane},
+        "roles": {role: {"keyids": [key.keyid], "threshold": 1} for role in roles},
+    }
+
+
+def main():
+    output = sys.argv[1] if len(sys.argv) > 1 else os.path.dirname(os.path.abspath(__file__))
+    sha256 = hashlib.sha256(FIRMWARE).hexdigest()
+    sha512 = hashlib.sha512(FIRMWARE).hexdigest()
+
+    image = Key("RSA")
+    image_targets = {
+        "version": 1,
+        "expires": EXPIRES,
+        "_type": "Targets",
+        "targets": {
+            "firmware-1": {
+                "hashes": {"sha256": sha256, "sha512": sha512},
+                "length": len(FIRMWARE),
+                "custom": {
+                    "hardwareIds": ["primary-hw"],
+                    "targetFormat": "BINARY",
+                    "version": "1",
+                    "name": "firmware",
+                    "uri": None,
+                    "createdAt": CREATED,
+                    "updatedAt": CREATED,
+                },
+            }
+        },
+    }
+
+    director = Key("ED25519")
+    director_targets = {
+        "_type": "Targets",
+        "expires": EXPIRES,
+        "version": 2,
+        "custom": {"correlationId": "urn:here-ota:mtu:8c3d1e0a"},
+        "targets": {
+            "firmware-1": {
+                "hashes": {"sha256": sha256},
+                "length": len(FIRMWARE),
+                "custom": {
+                    "ecuIdentifiers": {"primary-serial": {"hardwareId": "primary-hw"}},
+                    "targetFormat": "BINARY",
+                },
+            }
+        },
+    }
+
+    files = {
+        "repo/root.json": signed_by(
+            image, root(image, ["root", "snapshot", "targets", "timestamp"])
+        ),
+        "repo/targets.json": signed_by(image, image_targets),
+        "director/root.json": signed_by(director, root(director, ["root", "targets"])),
+        "director/targets.json": signed_by(director, director_targets),
+    }
+    for name, metadata in files.items():
+        path = os.path.join(output, name)
+        os.makedirs(os.path.dirname(path), exist_ok=True)
+        with open(path, "w") as file:
+            json.dump(metadata, file, indent=2)
+            file.write("\n")
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,129 @@
+use openssl::pkey::PKey;
+use oxidizr::canonical_json::CanonicalJson;
+use oxidizr::crypto::{Crypto, KeyType};
+use oxidizr::public_key::PublicKey;
+use oxidizr::schema;
+use oxidizr::sqlstorage::SQLStorage;
+use oxidizr::tuf_repository_type::RepositoryType;
+use oxidizr::tuf_verification::{CheckResult, MetadataVerifier};
+use rusqlite::{params, Connection};
+use serde_json::{json, Value};
+use tempfile::TempDir;
+
+struct SigningKey {
+    private: String,
+    pem: String,
+    hex: String,
+}
+
+impl SigningKey {
+    fn generate() -> Self {
+        let pkey = PKey::generate_ed25519().unwrap();
+        SigningKey {
+            private: String::from_utf8(pkey.private_key_to_pem_pkcs8().unwrap()).unwrap(),
+            pem: String::from_utf8(pkey.public_key_to_pem().unwrap()).unwrap(),
+            hex: hex::encode(pkey.raw_public_key().unwrap()),
+        }
+    }
+
+    fn sign(&self, signed: &Value) -> String {
+        let message = CanonicalJson::serialize(signed).unwrap();
+        let signature = Crypto::sign(&KeyType::Ed25519, &self.private, &message).unwrap();
+        openssl::base64::encode_block(&signature)
+    }
+}
+
+// Director root v1 listing each (key ID, public key) as a root key, signed under every key ID
+// by the key it was generated from
+fn director_root(keys: &[(String, String, &SigningKey)], threshold: u64) -> String {
+    let listed: serde_json::Map<String, Value> = keys
+        .iter()
+        .map(|(keyid, public, _)| {
+            (
+                keyid.clone(),
+                json!({ "keytype": "ED25519", "keyval": { "public": public } }),
+            )
+        })
+        .collect();
+    let keyids: Vec<&String> = keys.iter().map(|(keyid, _, _)| keyid).collect();
+    let signed = json!({
+        "_type": "Root",
+        "version": 1,
+        "expires": "2030-01-01T00:00:00Z",
+        "consistent_snapshot": false,
+        "keys": listed,
+        "roles": { "root": { "keyids": keyids, "threshold": threshold } },
+    });
+    let signatures: Vec<Value> = keys
+        .iter()
+        .map(|(keyid, _, key)| {
+            json!({ "keyid": keyid, "method": "ed25519", "sig": key.sign(&signed) })
+        })
+        .collect();
+    json!({ "signatures": signatures, "signed": signed }).to_string()
+}
+
+fn verify_root(root: &str) -> Vec<CheckResult> {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("sql.db");
+    let conn = Connection::open(&path).unwrap();
+    schema::create_unversioned(&conn).unwrap();
+    conn.execute(
+        "INSERT INTO meta(meta, repo, meta_type, version) VALUES (?, 1, 0, 1);",
+        params![root.as_bytes()],
+    )
+    .unwrap();
+
+    let storage = SQLStorage::new(&path, false).unwrap();
+    MetadataVerifier::new(&storage)
+        .verify_root_chain(RepositoryType::director())
+        .unwrap()
+}
+
+fn key_id(public: &str) -> String {
+    PublicKey::detect(public).key_id()
+}
+
+#[test]
+fn distinct_keys_meet_the_threshold() {
+    let (first, second) = (SigningKey::generate(), SigningKey::generate());
+    let root = director_root(
+        &[
+            (key_id(&first.hex), first.hex.clone(), &first),
+            (key_id(&second.hex), second.hex.clone(), &second),
+        ],
+        2,
+    );
+
+    let results = verify_root(&root);
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].error, None);
+}
+
+#[test]
+fn key_listed_under_several_ids_counts_once() {
+    let key = SigningKey::generate();
+    let listings = [
+        // The same value under its own ID and a made-up one
+        [
+            (key_id(&key.hex), key.hex.clone(), &key),
+            ("0".repeat(64), key.hex.clone(), &key),
+        ],
+        // The hex and PEM forms of the key, each under its own ID
+        [
+            (key_id(&key.hex), key.hex.clone(), &key),
+            (Crypto::sha256digest_hex(&key.pem), key.pem.clone(), &key),
+        ],
+    ];
+
+    for keys in listings {
+        let results = verify_root(&director_root(&keys, 2));
+        assert_eq!(results.len(), 1);
+        let error = results[0].error.as_deref().unwrap();
+        assert!(
+            error.contains("1 of 2 required root signatures are valid"),
+            "{}",
+            error
+        );
+    }
+}
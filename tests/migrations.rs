@@ -0,0 +1,65 @@
+use oxidizr::error::Error;
+use oxidizr::schema;
+use oxidizr::sqlstorage::SQLStorage;
+use rusqlite::Connection;
+use std::path::PathBuf;
+use tempfile::TempDir;
+
+fn storage(dir: &TempDir, version: Option<i32>) -> PathBuf {
+    let path = dir.path().join("sql.db");
+    let conn = Connection::open(&path).unwrap();
+    schema::create_unversioned(&conn).unwrap();
+    if let Some(version) = version {
+        conn.execute("INSERT INTO version(version) VALUES (?);", [version])
+            .unwrap();
+    }
+    path
+}
+
+fn file_names(dir: &TempDir) -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir(dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn migration_is_refused_and_storage_left_untouched() {
+    for version in [Some(0), Some(21), None] {
+        let dir = TempDir::new().unwrap();
+        let path = storage(&dir, version);
+        let before = std::fs::read(&path).unwrap();
+
+        let error = SQLStorage::new(&path, true).err().unwrap();
+        assert!(matches!(&error, Error::Migration(e) if e.contains("left untouched")));
+        assert_eq!(error.exit_code(), 13);
+        assert_eq!(std::fs::read(&path).unwrap(), before);
+        assert_eq!(file_names(&dir), ["sql.db"]);
+    }
+}
+
+#[test]
+fn storage_without_schema_is_not_migrated() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("sql.db");
+    Connection::open(&path)
+        .unwrap()
+        .execute_batch("CREATE TABLE device_info(device_id TEXT);")
+        .unwrap();
+
+    assert!(matches!(
+        SQLStorage::new(&path, true),
+        Err(Error::SchemaUnknown(_))
+    ));
+}
+
+#[test]
+fn storage_is_read_without_migrating() {
+    let dir = TempDir::new().unwrap();
+    let path = storage(&dir, Some(21));
+
+    let storage = SQLStorage::new(&path, false).unwrap();
+    assert_eq!(storage.schema().version, Some(21));
+}
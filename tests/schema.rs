@@ -0,0 +1,80 @@
+use oxidizr::error::Error;
+use oxidizr::schema::{self, Feature, SchemaInfo};
+use rusqlite::Connection;
+
+fn storage(version: i32) -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    schema::create_unversioned(&conn).unwrap();
+    conn.execute("INSERT INTO version(version) VALUES (?);", [version])
+        .unwrap();
+    conn
+}
+
+#[test]
+fn full_schema_supports_every_feature() {
+    let schema = SchemaInfo::detect(&storage(21)).unwrap();
+    assert_eq!(schema.version, Some(21));
+    assert!(schema.unavailable().is_empty());
+    assert!(schema.check().is_ok());
+}
+
+#[test]
+fn created_storage_records_no_version() {
+    let conn = Connection::open_in_memory().unwrap();
+    schema::create_unversioned(&conn).unwrap();
+
+    let schema = SchemaInfo::detect(&conn).unwrap();
+    assert_eq!(schema.version, None);
+    assert!(schema.unavailable().is_empty());
+    assert!(schema.check().is_ok());
+    assert!(schema
+        .to_string()
+        .starts_with("Schema version: not recorded"));
+}
+
+#[test]
+fn features_are_detected_from_the_tables() {
+    // Any version, the tables alone tell what is available
+    for version in [0, 30] {
+        let conn = storage(version);
+        conn.execute_batch(
+            "DROP TABLE delegations; ALTER TABLE installed_versions DROP COLUMN correlation_id;",
+        )
+        .unwrap();
+
+        let schema = SchemaInfo::detect(&conn).unwrap();
+        assert_eq!(
+            schema.unavailable(),
+            [Feature::InstalledVersions, Feature::Delegations]
+        );
+        assert!(schema.check().is_ok());
+    }
+}
+
+#[test]
+fn missing_required_tables_are_refused() {
+    let conn = storage(21);
+    conn.execute_batch("DROP TABLE tls_creds;").unwrap();
+
+    let schema = SchemaInfo::detect(&conn).unwrap();
+    let error = schema.check().unwrap_err();
+    assert!(matches!(error, Error::SchemaTooOld { .. }));
+    assert_eq!(error.exit_code(), 5);
+}
+
+#[test]
+fn missing_version_is_an_unknown_schema() {
+    let conn = Connection::open_in_memory().unwrap();
+    conn.execute_batch("CREATE TABLE device_info(device_id TEXT);")
+        .unwrap();
+    let error = SchemaInfo::detect(&conn).unwrap_err();
+    assert!(matches!(error, Error::SchemaUnknown(_)));
+    assert_eq!(error.exit_code(), 16);
+
+    conn.execute_batch("CREATE TABLE version(version INTEGER); INSERT INTO version VALUES (-1);")
+        .unwrap();
+    assert!(matches!(
+        SchemaInfo::detect(&conn),
+        Err(Error::SchemaUnknown(_))
+    ));
+}
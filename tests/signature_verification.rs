@@ -0,0 +1,269 @@
+use oxidizr::canonical_json::CanonicalJson;
+use oxidizr::crypto::{Crypto, KeyType, SignatureEncoding, SignatureScheme, VerificationError};
+use oxidizr::public_key::PublicKey;
+use oxidizr::tuf_metadata::{Metadata, Root, Targets};
+use serde_json::Value;
+
+// Metadata in the form aktualizr stores it: RSA-PSS signed image repository, Ed25519 signed
+// Director, base64 signatures with aktualizr's method names, pretty printed with unsorted keys.
+// Generated with fresh keys by fixtures/metadata/generate.py, not taken from a device: their
+// key IDs come from the same rule as PublicKey::key_id, so they are not checked here, see
+// tests/captured_metadata.rs.
+const IMAGE_ROOT: &str = include_str!("fixtures/metadata/repo/root.json");
+const IMAGE_TARGETS: &str = include_str!("fixtures/metadata/repo/targets.json");
+const DIRECTOR_ROOT: &str = include_str!("fixtures/metadata/director/root.json");
+const DIRECTOR_TARGETS: &str = include_str!("fixtures/metadata/director/targets.json");
+
+fn root_key(root: &Metadata<Root>, role: &str) -> PublicKey {
+    let keyid = &root.signed.roles[role].keyids[0];
+    root.signed.keys[keyid].to_public_key().unwrap()
+}
+
+#[test]
+fn verifies_rsa_signed_image_metadata() {
+    let root = Metadata::<Root>::parse(IMAGE_ROOT).unwrap();
+    let key = root_key(&root, "root");
+    assert_eq!(root.verify_signature(&root.signatures[0], &key), Ok(()));
+
+    let targets = Metadata::<Targets>::parse(IMAGE_TARGETS).unwrap();
+    let key = root_key(&root, "targets");
+    assert_eq!(
+        targets.verify_signature(&targets.signatures[0], &key),
+        Ok(())
+    );
+}
+
+#[test]
+fn verifies_ed25519_signed_director_metadata() {
+    let root = Metadata::<Root>::parse(DIRECTOR_ROOT).unwrap();
+    let key = root_key(&root, "root");
+    assert_eq!(root.verify_signature(&root.signatures[0], &key), Ok(()));
+
+    let targets = Metadata::<Targets>::parse(DIRECTOR_TARGETS).unwrap();
+    let key = root_key(&root, "targets");
+    assert_eq!(
+        targets.verify_signature(&targets.signatures[0], &key),
+        Ok(())
+    );
+}
+
+#[test]
+fn key_id_is_digest_of_canonical_uptane_form() {
+    for root in [IMAGE_ROOT, DIRECTOR_ROOT] {
+        let root = Metadata::<Root>::parse(root).unwrap();
+        let key = root_key(&root, "root");
+        let canonical = CanonicalJson::serialize(&key.to_uptane()).unwrap();
+        assert_eq!(key.key_id(), Crypto::sha256digest_hex(&canonical));
+    }
+}
+
+#[test]
+fn pem_ed25519_key_is_kept_and_identified_by_its_hex_form() {
+    let pkey = openssl::pkey::PKey::generate_ed25519().unwrap();
+    let pem = String::from_utf8(pkey.public_key_to_pem().unwrap()).unwrap();
+    let hex = hex::encode(pkey.raw_public_key().unwrap());
+
+    let stored = PublicKey::detect(&pem);
+    assert_eq!(stored.key_type(), &KeyType::Ed25519);
+    assert_eq!(stored.value(), pem);
+    assert_eq!(stored.to_uptane()["keyval"]["public"], hex.as_str());
+    assert_eq!(stored.key_id(), PublicKey::detect(&hex).key_id());
+}
+
+#[test]
+fn verifies_against_canonical_form_of_signed() {
+    // Re-serializing with sorted keys and no whitespace does not change what was signed
+    let value: Value = serde_json::from_str(DIRECTOR_TARGETS).unwrap();
+    let compact = Metadata::<Targets>::parse(&value.to_string()).unwrap();
+    let root = Metadata::<Root>::parse(DIRECTOR_ROOT).unwrap();
+    assert_eq!(
+        compact.verify_signature(&compact.signatures[0], &root_key(&root, "targets")),
+        Ok(())
+    );
+}
+
+#[test]
+fn rejects_modified_signed() {
+    let mut value: Value = serde_json::from_str(DIRECTOR_TARGETS).unwrap();
+    value["signed"]["version"] = Value::from(3);
+    let targets = Metadata::<Targets>::from_value(value).unwrap();
+    let root = Metadata::<Root>::parse(DIRECTOR_ROOT).unwrap();
+    assert_eq!(
+        targets.verify_signature(&targets.signatures[0], &root_key(&root, "targets")),
+        Err(VerificationError::BadSignature)
+    );
+
+    let mut value: Value = serde_json::from_str(IMAGE_TARGETS).unwrap();
+    value["signed"]["targets"]["firmware-1"]["length"] = Value::from(12);
+    let targets = Metadata::<Targets>::from_value(value).unwrap();
+    let root = Metadata::<Root>::parse(IMAGE_ROOT).unwrap();
+    assert_eq!(
+        targets.verify_signature(&targets.signatures[0], &root_key(&root, "targets")),
+        Err(VerificationError::BadSignature)
+    );
+}
+
+#[test]
+fn accepts_hex_encoded_signatures() {
+    let mut root = Metadata::<Root>::parse(DIRECTOR_ROOT).unwrap();
+    let key = root_key(&root, "root");
+    let signature = &mut root.signatures[0];
+    let raw = SignatureEncoding::Base64.decode(&signature.sig).unwrap();
+    signature.sig = hex::encode(raw);
+    assert_eq!(
+        SignatureEncoding::detect(&signature.sig),
+        SignatureEncoding::Hex
+    );
+    assert_eq!(root.verify_signature(&root.signatures[0], &key), Ok(()));
+}
+
+#[test]
+fn uses_key_type_scheme_when_method_is_missing() {
+    let mut root = Metadata::<Root>::parse(IMAGE_ROOT).unwrap();
+    let key = root_key(&root, "root");
+    root.signatures[0].method.clear();
+    assert_eq!(root.verify_signature(&root.signatures[0], &key), Ok(()));
+}
+
+#[test]
+fn rejects_scheme_not_matching_key() {
+    let mut root = Metadata::<Root>::parse(IMAGE_ROOT).unwrap();
+    let key = root_key(&root, "root");
+    root.signatures[0].method = "ed25519".to_string();
+    assert!(matches!(
+        root.verify_signature(&root.signatures[0], &key),
+        Err(VerificationError::SchemeMismatch {
+            scheme: SignatureScheme::Ed25519,
+            ..
+        })
+    ));
+
+    root.signatures[0].method = "rsassa-pkcs1v15-sha256".to_string();
+    assert_eq!(
+        root.verify_signature(&root.signatures[0], &key),
+        Err(VerificationError::UnsupportedScheme(
+            "rsassa-pkcs1v15-sha256".to_string()
+        ))
+    );
+}
+
+#[test]
+fn malformed_input_is_an_error() {
+    let root = Metadata::<Root>::parse(IMAGE_ROOT).unwrap();
+    let message = b"message";
+
+    assert!(matches!(
+        Crypto::verify(
+            SignatureScheme::RsassaPssSha256,
+            "-----BEGIN PUBLIC KEY-----\ngarbage\n-----END PUBLIC KEY-----\n",
+            &[0; 256],
+            message
+        ),
+        Err(VerificationError::InvalidKey(_))
+    ));
+    assert!(matches!(
+        Crypto::verify(SignatureScheme::Ed25519, "not hex", &[0; 64], message),
+        Err(VerificationError::InvalidKey(_))
+    ));
+    assert_eq!(
+        Crypto::verify(
+            SignatureScheme::RsassaPssSha256,
+            root_key(&root, "root").value(),
+            b"short",
+            message
+        ),
+        Err(VerificationError::BadSignature)
+    );
+    assert!(matches!(
+        SignatureEncoding::Base64.decode("not base64!"),
+        Err(VerificationError::InvalidSignature(_))
+    ));
+}
+
+// Freshly generated P-256 key pair, PEM encoded
+fn p256_key_pair() -> (String, String) {
+    let group = openssl::ec::EcGroup::from_curve_name(openssl::nid::Nid::X9_62_PRIME256V1).unwrap();
+    let key =
+        openssl::pkey::PKey::from_ec_key(openssl::ec::EcKey::generate(&group).unwrap()).unwrap();
+    (
+        String::from_utf8(key.public_key_to_pem().unwrap()).unwrap(),
+        String::from_utf8(key.private_key_to_pem_pkcs8().unwrap()).unwrap(),
+    )
+}
+
+#[test]
+fn detects_ecdsa_p256_keys() {
+    let (public, _) = p256_key_pair();
+    let detected = PublicKey::detect(&public);
+    assert_eq!(detected.key_type(), &KeyType::EcdsaP256);
+    assert_eq!(detected.to_uptane()["keytype"], "ecdsa-sha2-nistp256");
+
+    let from_json = PublicKey::from_json(&detected.to_uptane()).unwrap();
+    assert_eq!(from_json.key_type(), &KeyType::EcdsaP256);
+    assert_eq!(from_json.key_id(), detected.key_id());
+    let canonical = CanonicalJson::serialize(&detected.to_uptane()).unwrap();
+    assert_eq!(detected.key_id(), Crypto::sha256digest_hex(&canonical));
+
+    // Other curves are not Uptane key types
+    let group = openssl::ec::EcGroup::from_curve_name(openssl::nid::Nid::SECP384R1).unwrap();
+    let p384 = openssl::ec::EcKey::generate(&group).unwrap();
+    let p384 = String::from_utf8(p384.public_key_to_pem().unwrap()).unwrap();
+    assert!(PublicKey::from_json(&serde_json::json!({
+        "keytype": "ecdsa-sha2-nistp256",
+        "keyval": { "public": p384 },
+    }))
+    .is_err());
+}
+
+#[test]
+fn verifies_der_and_raw_ecdsa_p256_signatures() {
+    let (public, private) = p256_key_pair();
+    let key = PublicKey::detect(&public);
+    let message = "message";
+    let der = Crypto::sign(&KeyType::EcdsaP256, &private, message).unwrap();
+
+    let scheme = SignatureScheme::EcdsaSha2Nistp256;
+    assert_eq!(
+        key.verify(
+            scheme,
+            &hex::encode(&der),
+            SignatureEncoding::Hex,
+            message.as_bytes()
+        ),
+        Ok(())
+    );
+
+    let signature = openssl::ecdsa::EcdsaSig::from_der(&der).unwrap();
+    let mut raw = signature.r().to_vec_padded(32).unwrap();
+    raw.extend(signature.s().to_vec_padded(32).unwrap());
+    assert_eq!(
+        key.verify(
+            scheme,
+            &hex::encode(&raw),
+            SignatureEncoding::Hex,
+            message.as_bytes()
+        ),
+        Ok(())
+    );
+
+    raw[63] ^= 1;
+    assert_eq!(
+        key.verify(
+            scheme,
+            &hex::encode(&raw),
+            SignatureEncoding::Hex,
+            message.as_bytes()
+        ),
+        Err(VerificationError::BadSignature)
+    );
+    assert_eq!(
+        key.verify(
+            scheme,
+            &hex::encode(&der[..40]),
+            SignatureEncoding::Hex,
+            message.as_bytes()
+        ),
+        Err(VerificationError::BadSignature)
+    );
+    assert_eq!(key.verify_private_key(&private), Ok(()));
+}
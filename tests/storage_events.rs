@@ -0,0 +1,158 @@
+use oxidizr::schema;
+use oxidizr::sqlstorage::SQLStorage;
+use oxidizr::storage_events::{StorageEventKind, StorageState};
+use oxidizr::tuf_repository_type::RepositoryType;
+use oxidizr::tuf_roles::Role;
+use rusqlite::{params, Connection};
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+fn targets(version: i32) -> Vec<u8> {
+    serde_json::json!({
+        "signatures": [],
+        "signed": {
+            "_type": "Targets",
+            "version": version,
+            "expires": "2030-01-01T00:00:00Z",
+            "targets": {},
+        },
+    })
+    .to_string()
+    .into_bytes()
+}
+
+// Unregistered storage with one ECU and nothing else, and a connection to change it with
+fn storage(dir: &TempDir) -> (PathBuf, Connection) {
+    let path = dir.path().join("sql.db");
+    let conn = Connection::open(&path).unwrap();
+    schema::create_unversioned(&conn).unwrap();
+    conn.execute_batch(
+        "INSERT INTO device_info(unique_mark, device_id, is_registered) VALUES (0, 'device', 0);
+         INSERT INTO ecus(id, serial, hardware_id, is_primary) VALUES (0, 'primary', 'primary-hw', 1);",
+    )
+    .unwrap();
+    (path, conn)
+}
+
+fn load(path: &Path) -> StorageState {
+    StorageState::load(&SQLStorage::new(path, false).unwrap()).unwrap()
+}
+
+fn changes(older: &StorageState, newer: &StorageState) -> Vec<StorageEventKind> {
+    older
+        .changes(newer)
+        .into_iter()
+        .map(|event| event.kind)
+        .collect()
+}
+
+#[test]
+fn unchanged_storage_has_no_events() {
+    let dir = TempDir::new().unwrap();
+    let (path, _) = storage(&dir);
+
+    assert_eq!(changes(&load(&path), &load(&path)), []);
+}
+
+#[test]
+fn registration_is_reported_both_ways() {
+    let dir = TempDir::new().unwrap();
+    let (path, conn) = storage(&dir);
+    let unregistered = load(&path);
+
+    conn.execute("UPDATE device_info SET is_registered = 1;", [])
+        .unwrap();
+    let registered = load(&path);
+
+    assert_eq!(
+        changes(&unregistered, &registered),
+        [StorageEventKind::RegistrationChanged { registered: true }]
+    );
+    assert_eq!(
+        changes(&registered, &unregistered),
+        [StorageEventKind::RegistrationChanged { registered: false }]
+    );
+}
+
+#[test]
+fn new_metadata_and_delegations_are_reported() {
+    let dir = TempDir::new().unwrap();
+    let (path, conn) = storage(&dir);
+    conn.execute(
+        "INSERT INTO meta(meta, repo, meta_type, version) VALUES (?, 1, 2, 1);",
+        params![targets(1)],
+    )
+    .unwrap();
+    let before = load(&path);
+
+    conn.execute(
+        "INSERT INTO meta(meta, repo, meta_type, version) VALUES (?, 1, 2, 2);",
+        params![targets(2)],
+    )
+    .unwrap();
+    conn.execute(
+        "INSERT INTO delegations(meta, role_name) VALUES (?, 'team-a');",
+        params![targets(4)],
+    )
+    .unwrap();
+
+    assert_eq!(
+        changes(&before, &load(&path)),
+        [
+            StorageEventKind::MetadataStored {
+                repo: RepositoryType::director().to_string(),
+                role: Role::targets().to_string(),
+                version: 2,
+            },
+            StorageEventKind::MetadataStored {
+                repo: RepositoryType::image().to_string(),
+                role: "team-a".to_owned(),
+                version: 4,
+            },
+        ]
+    );
+}
+
+#[test]
+fn installed_version_changes_are_reported() {
+    let dir = TempDir::new().unwrap();
+    let (path, conn) = storage(&dir);
+    let before = load(&path);
+
+    conn.execute_batch(
+        "INSERT INTO installed_versions(ecu_serial, sha256, name, hashes, is_current, is_pending)
+         VALUES ('primary', 'aa', 'image-1', '', 1, 0), ('primary', 'bb', 'image-2', '', 0, 1);",
+    )
+    .unwrap();
+
+    assert_eq!(
+        changes(&before, &load(&path)),
+        [StorageEventKind::InstalledVersionChanged {
+            ecu_serial: "primary".to_owned(),
+            current: Some("image-1 (aa)".to_owned()),
+            pending: Some("image-2 (bb)".to_owned()),
+        }]
+    );
+}
+
+#[test]
+fn tls_rotation_is_reported_only_when_credentials_existed() {
+    let dir = TempDir::new().unwrap();
+    let (path, conn) = storage(&dir);
+    let without = load(&path);
+
+    conn.execute(
+        "INSERT INTO tls_creds(ca_cert, client_cert, client_pkey) VALUES (x'6361', x'63657274', x'706b6579');",
+        [],
+    )
+    .unwrap();
+    let provisioned = load(&path);
+    assert_eq!(changes(&without, &provisioned), []);
+
+    conn.execute("UPDATE tls_creds SET client_cert = x'6e6577';", [])
+        .unwrap();
+    assert_eq!(
+        changes(&provisioned, &load(&path)),
+        [StorageEventKind::TlsCredentialsRotated]
+    );
+}
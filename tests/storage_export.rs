@@ -0,0 +1,274 @@
+use oxidizr::archive::Archive;
+use oxidizr::config::Config;
+use oxidizr::fs_import::{FsImporter, FsLayout};
+use oxidizr::schema;
+use oxidizr::sqlstorage::SQLStorage;
+use oxidizr::storage_export::StorageExporter;
+use oxidizr::tuf_repository_type::RepositoryType;
+use oxidizr::tuf_roles::Role;
+use rusqlite::{params, Connection};
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+// (repo, meta type, version) of aktualizr's meta table, with the role the metadata is for
+const METADATA: [(i32, i32, i32, &str); 6] = [
+    (0, 0, 1, "Root"),
+    (0, 0, 2, "Root"),
+    (0, 3, 7, "Timestamp"),
+    (0, 1, 5, "Snapshot"),
+    (0, 2, 3, "Targets"),
+    (1, 0, 1, "Root"),
+];
+
+fn metadata(kind: &str, version: i32) -> String {
+    let mut signed = serde_json::json!({
+        "_type": kind,
+        "version": version,
+        "expires": "2030-01-01T00:00:00Z",
+    });
+    match kind {
+        "Root" => {
+            signed["keys"] = serde_json::json!({});
+            signed["roles"] = serde_json::json!({});
+        }
+        "Targets" => signed["targets"] = serde_json::json!({}),
+        _ => signed["meta"] = serde_json::json!({}),
+    }
+    serde_json::json!({ "signatures": [], "signed": signed }).to_string()
+}
+
+fn storage(dir: &TempDir) -> PathBuf {
+    let path = dir.path().join("sql.db");
+    let conn = Connection::open(&path).unwrap();
+    schema::create_unversioned(&conn).unwrap();
+    conn.execute_batch(
+        "INSERT INTO device_info(unique_mark, device_id, is_registered) VALUES (0, 'device', 1);
+         INSERT INTO ecus(id, serial, hardware_id, is_primary) VALUES (0, 'primary', 'primary-hw', 1);
+         INSERT INTO ecus(id, serial, hardware_id, is_primary) VALUES (1, 'secondary', 'secondary-hw', 0);
+         INSERT INTO tls_creds(ca_cert, client_cert, client_pkey) VALUES (x'6361', x'63657274', x'706b6579');",
+    )
+    .unwrap();
+    let key = openssl::rsa::Rsa::generate(2048).unwrap();
+    conn.execute(
+        "INSERT INTO primary_keys(unique_mark, private, public) VALUES (0, ?, ?);",
+        params![
+            String::from_utf8(key.private_key_to_pem().unwrap()).unwrap(),
+            String::from_utf8(key.public_key_to_pem().unwrap()).unwrap()
+        ],
+    )
+    .unwrap();
+    for (repo, meta_type, version, kind) in METADATA {
+        conn.execute(
+            "INSERT INTO meta(meta, repo, meta_type, version) VALUES (?, ?, ?, ?);",
+            params![
+                metadata(kind, version).into_bytes(),
+                repo,
+                meta_type,
+                version
+            ],
+        )
+        .unwrap();
+    }
+    for role_name in ["team-a", "../escaped", "/etc/absolute"] {
+        conn.execute(
+            "INSERT INTO delegations(meta, role_name) VALUES (?, ?);",
+            params![metadata("Targets", 4).into_bytes(), role_name],
+        )
+        .unwrap();
+    }
+    path
+}
+
+fn files(root: &Path) -> Vec<String> {
+    let mut files = Vec::new();
+    let mut directories = vec![root.to_path_buf()];
+    while let Some(directory) = directories.pop() {
+        for entry in std::fs::read_dir(directory).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                directories.push(path);
+            } else {
+                files.push(path.strip_prefix(root).unwrap().display().to_string());
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+#[test]
+fn exports_metadata_in_filesystem_storage_layout() {
+    let dir = TempDir::new().unwrap();
+    let storage = SQLStorage::new(&storage(&dir), false).unwrap();
+    let output = dir.path().join("export");
+    StorageExporter::new(&storage, false)
+        .export()
+        .unwrap()
+        .write(&output)
+        .unwrap();
+
+    assert_eq!(
+        files(&output),
+        [
+            "client.pem",
+            "device.json",
+            "device_id",
+            "ecukey.pub",
+            "is_registered",
+            "metadata/director/root_v1.json",
+            "metadata/repo/delegations/team-a_v4.json",
+            "metadata/repo/root_v1.json",
+            "metadata/repo/root_v2.json",
+            "metadata/repo/snapshot_v5.json",
+            "metadata/repo/targets_v3.json",
+            "metadata/repo/timestamp_v7.json",
+            "primary_ecu_hardware_id",
+            "primary_ecu_serial",
+            "root.crt",
+            "secondaries_list",
+        ]
+    );
+    assert!(!dir.path().join("escaped_v4.json").exists());
+}
+
+#[test]
+fn export_reads_back_with_the_importer() {
+    let dir = TempDir::new().unwrap();
+    let storage = SQLStorage::new(&storage(&dir), false).unwrap();
+    let output = dir.path().join("export");
+    StorageExporter::new(&storage, true)
+        .export()
+        .unwrap()
+        .write(&output)
+        .unwrap();
+
+    // The importer reads it with the layout of the default configuration
+    let layout = FsLayout::from_config(&output, &Config::default());
+    let imported = dir.path().join("imported.db");
+    FsImporter::new(layout).import(&imported).unwrap();
+
+    let imported = SQLStorage::new(&imported, false).unwrap();
+    assert_eq!(
+        imported.load_device_id().unwrap(),
+        storage.load_device_id().unwrap()
+    );
+    assert!(imported.load_ecu_registered().unwrap());
+    let ecus = |storage: &SQLStorage| -> Vec<(String, String, bool)> {
+        storage
+            .load_ecus()
+            .unwrap()
+            .into_iter()
+            .map(|ecu| {
+                (
+                    ecu.serial.to_string(),
+                    ecu.hardware_id.to_string().to_owned(),
+                    ecu.is_primary,
+                )
+            })
+            .collect()
+    };
+    assert_eq!(ecus(&imported), ecus(&storage));
+
+    let (public, private) = storage.load_primary_keys().unwrap().unwrap();
+    let (imported_public, imported_private) = imported.load_primary_keys().unwrap().unwrap();
+    assert_eq!(imported_public.value(), public.value());
+    assert_eq!(*imported_private, *private);
+
+    let tls = |storage: &SQLStorage| {
+        let (mut ca, mut cert, mut pkey) = (Vec::new(), Vec::new(), Default::default());
+        assert!(storage
+            .load_tls_credentials(&mut ca, &mut cert, &mut pkey)
+            .unwrap());
+        (ca, cert, pkey)
+    };
+    assert_eq!(tls(&imported), tls(&storage));
+
+    for (repo, role) in SQLStorage::stored_roles() {
+        assert_eq!(
+            imported.load_metadata_versions(repo, role.clone()).unwrap(),
+            storage.load_metadata_versions(repo, role).unwrap()
+        );
+    }
+}
+
+#[test]
+fn export_without_secrets_reads_back_with_the_public_key_only() {
+    let dir = TempDir::new().unwrap();
+    let storage = SQLStorage::new(&storage(&dir), false).unwrap();
+    let output = dir.path().join("export");
+    StorageExporter::new(&storage, false)
+        .export()
+        .unwrap()
+        .write(&output)
+        .unwrap();
+
+    let imported = dir.path().join("imported.db");
+    FsImporter::new(FsLayout::from_config(&output, &Config::default()))
+        .import(&imported)
+        .unwrap();
+    let imported = SQLStorage::new(&imported, false).unwrap();
+    assert_eq!(
+        imported.load_primary_public().unwrap(),
+        storage.load_primary_public().unwrap()
+    );
+    assert!(imported.load_primary_private().unwrap().is_none());
+    assert!(imported.load_primary_keys().unwrap().is_none());
+}
+
+#[test]
+fn json_export_holds_private_keys_only_with_secrets() {
+    let dir = TempDir::new().unwrap();
+    let storage = SQLStorage::new(&storage(&dir), false).unwrap();
+    let private = storage.load_primary_private().unwrap().unwrap();
+
+    for secrets in [false, true] {
+        let output = dir.path().join(format!("export-{}.json", secrets));
+        StorageExporter::new(&storage, secrets)
+            .export()
+            .unwrap()
+            .write(&output)
+            .unwrap();
+
+        let document: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&output).unwrap()).unwrap();
+        assert!(document["ecukey.pub"].is_string());
+        assert_eq!(document["pkey.pem"].as_str(), secrets.then_some("pkey"));
+        assert_eq!(
+            document["ecukey.der"].as_str(),
+            secrets.then_some(private.as_str())
+        );
+    }
+}
+
+#[test]
+fn role_names_leaving_the_export_are_refused() {
+    let image = RepositoryType::image();
+    assert_eq!(
+        StorageExporter::metadata_path(image, Role::ROOT, 2).unwrap(),
+        Path::new("repo/root_v2.json")
+    );
+    assert_eq!(
+        StorageExporter::metadata_path(RepositoryType::director(), Role::TARGETS, 3).unwrap(),
+        Path::new("director/targets_v3.json")
+    );
+    for role in ["", "a/b", "..", "/etc/passwd", "a\\b"] {
+        assert!(
+            StorageExporter::delegation_path(role, 1).is_err(),
+            "{}",
+            role
+        );
+    }
+}
+
+#[test]
+fn archive_paths_leaving_the_output_are_refused() {
+    let dir = TempDir::new().unwrap();
+    for path in ["../outside", "/tmp/absolute", "a/../../b", ""] {
+        let mut archive = Archive::new();
+        archive.add(path, "content");
+        let output = dir.path().join("out");
+        assert!(archive.write(&output).is_err(), "{}", path);
+        assert!(archive.write(&dir.path().join("out.tar")).is_err());
+        assert!(!dir.path().join("outside").exists());
+    }
+}
@@ -0,0 +1,140 @@
+use openssl::asn1::Asn1Time;
+use openssl::ec::{EcGroup, EcKey};
+use openssl::hash::MessageDigest;
+use openssl::nid::Nid;
+use openssl::pkey::{PKey, Private};
+use openssl::rsa::Rsa;
+use openssl::x509::extension::SubjectAlternativeName;
+use openssl::x509::{X509NameBuilder, X509};
+use oxidizr::schema;
+use oxidizr::sqlstorage::SQLStorage;
+use oxidizr::tls_consistency::TlsConsistencyChecker;
+use rusqlite::{params, Connection};
+use tempfile::TempDir;
+
+const DEVICE_ID: &str = "device";
+
+fn ec_key() -> PKey<Private> {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+    PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap()
+}
+
+// Self-signed client certificate with the common name and DNS subject alternative names
+fn certificate(common_name: &str, alt_names: &[&str], key: &PKey<Private>) -> X509 {
+    let mut name = X509NameBuilder::new().unwrap();
+    name.append_entry_by_text("CN", common_name).unwrap();
+    let name = name.build();
+
+    let mut builder = X509::builder().unwrap();
+    builder.set_version(2).unwrap();
+    builder.set_subject_name(&name).unwrap();
+    builder.set_issuer_name(&name).unwrap();
+    builder.set_pubkey(key).unwrap();
+    builder
+        .set_not_before(&Asn1Time::days_from_now(0).unwrap())
+        .unwrap();
+    builder
+        .set_not_after(&Asn1Time::days_from_now(365).unwrap())
+        .unwrap();
+    if !alt_names.is_empty() {
+        let mut san = SubjectAlternativeName::new();
+        for alt_name in alt_names {
+            san.dns(alt_name);
+        }
+        let san = san.build(&builder.x509v3_context(None, None)).unwrap();
+        builder.append_extension(san).unwrap();
+    }
+    builder.sign(key, MessageDigest::sha256()).unwrap();
+    builder.build()
+}
+
+// Error of each consistency check of the stored credentials, by check name
+fn check(cert: &X509, key: &PKey<Private>) -> Vec<(&'static str, Option<String>)> {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("sql.db");
+    let conn = Connection::open(&path).unwrap();
+    schema::create_unversioned(&conn).unwrap();
+    conn.execute(
+        "INSERT INTO device_info(unique_mark, device_id, is_registered) VALUES (0, ?, 1);",
+        params![DEVICE_ID],
+    )
+    .unwrap();
+    conn.execute(
+        "INSERT INTO tls_creds(ca_cert, client_cert, client_pkey) VALUES (?, ?, ?);",
+        params![
+            cert.to_pem().unwrap(),
+            cert.to_pem().unwrap(),
+            key.private_key_to_pem_pkcs8().unwrap()
+        ],
+    )
+    .unwrap();
+
+    let storage = SQLStorage::new(&path, false).unwrap();
+    TlsConsistencyChecker::new(&storage)
+        .check()
+        .unwrap()
+        .unwrap()
+        .into_iter()
+        .map(|result| (result.check, result.error))
+        .collect()
+}
+
+fn error(results: &[(&'static str, Option<String>)], check: &str) -> Option<String> {
+    results
+        .iter()
+        .find(|(name, _)| *name == check)
+        .unwrap_or_else(|| panic!("no {} check", check))
+        .1
+        .clone()
+}
+
+#[test]
+fn consistent_credentials_pass_every_check() {
+    let key = ec_key();
+    let results = check(&certificate(DEVICE_ID, &[], &key), &key);
+
+    assert_eq!(results.len(), 5);
+    for (check, error) in results {
+        assert_eq!(error, None, "{}", check);
+    }
+}
+
+#[test]
+fn device_id_is_matched_against_the_common_name_or_a_san() {
+    const CHECK: &str = "Client certificate matches device ID";
+    let key = ec_key();
+
+    let results = check(&certificate("gateway", &["other", DEVICE_ID], &key), &key);
+    assert_eq!(error(&results, CHECK), None);
+
+    let results = check(&certificate("other-device", &["other"], &key), &key);
+    assert_eq!(
+        error(&results, CHECK).as_deref(),
+        Some("certificate has CN 'other-device' and DNS SANs 'other', device ID is 'device'")
+    );
+
+    let results = check(&certificate("other-device", &[], &key), &key);
+    assert_eq!(
+        error(&results, CHECK).as_deref(),
+        Some("certificate has CN 'other-device' and no DNS SAN, device ID is 'device'")
+    );
+}
+
+#[test]
+fn private_key_must_belong_to_the_certificate() {
+    const CHECK: &str = "Client private key matches certificate";
+    let cert = certificate(DEVICE_ID, &[], &ec_key());
+
+    let results = check(&cert, &ec_key());
+    assert_eq!(
+        error(&results, CHECK).as_deref(),
+        Some("private key does not belong to the certificate's public key")
+    );
+
+    let rsa = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+    let results = check(&cert, &rsa);
+    assert_eq!(
+        error(&results, CHECK).as_deref(),
+        Some("certificate holds an EC public key, the private key is RSA")
+    );
+}
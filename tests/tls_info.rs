@@ -0,0 +1,87 @@
+use chrono::Duration;
+use openssl::asn1::Asn1Time;
+use openssl::ec::{EcGroup, EcKey};
+use openssl::hash::MessageDigest;
+use openssl::nid::Nid;
+use openssl::pkey::{PKey, Private};
+use openssl::x509::extension::BasicConstraints;
+use openssl::x509::{X509NameBuilder, X509};
+use oxidizr::tls_info::{ChainStatus, TlsInspector};
+
+// Certificate for the common name valid for a year, signed by the issuer or self-signed
+fn certificate(
+    common_name: &str,
+    key: &PKey<Private>,
+    issuer: Option<(&X509, &PKey<Private>)>,
+) -> X509 {
+    let mut name = X509NameBuilder::new().unwrap();
+    name.append_entry_by_text("CN", common_name).unwrap();
+    let name = name.build();
+
+    let mut builder = X509::builder().unwrap();
+    builder.set_version(2).unwrap();
+    builder.set_subject_name(&name).unwrap();
+    builder.set_pubkey(key).unwrap();
+    builder
+        .set_not_before(&Asn1Time::days_from_now(0).unwrap())
+        .unwrap();
+    builder
+        .set_not_after(&Asn1Time::days_from_now(365).unwrap())
+        .unwrap();
+    match issuer {
+        Some((issuer, issuer_key)) => {
+            builder.set_issuer_name(issuer.subject_name()).unwrap();
+            builder.sign(issuer_key, MessageDigest::sha256()).unwrap();
+        }
+        None => {
+            builder
+                .append_extension(BasicConstraints::new().critical().ca().build().unwrap())
+                .unwrap();
+            builder.set_issuer_name(&name).unwrap();
+            builder.sign(key, MessageDigest::sha256()).unwrap();
+        }
+    }
+    builder.build()
+}
+
+fn key() -> PKey<Private> {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+    PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap()
+}
+
+fn ca(common_name: &str) -> (X509, PKey<Private>) {
+    let key = key();
+    (certificate(common_name, &key, None), key)
+}
+
+fn chain(ca: &X509, client: &X509) -> ChainStatus {
+    TlsInspector::new(Duration::days(30))
+        .inspect(&ca.to_pem().unwrap(), &client.to_pem().unwrap())
+        .unwrap()
+        .chain
+}
+
+#[test]
+fn client_certificate_verifies_against_its_ca() {
+    let (ca, ca_key) = ca("root CA");
+    let client = certificate("device", &key(), Some((&ca, &ca_key)));
+
+    assert_eq!(chain(&ca, &client), ChainStatus::Verified);
+}
+
+#[test]
+fn client_certificate_fails_against_another_ca() {
+    let (ca, ca_key) = ca("root CA");
+    let client = certificate("device", &key(), Some((&ca, &ca_key)));
+    // Another CA under the same name, only its key differs
+    let (other, _) = self::ca("root CA");
+
+    match chain(&other, &client) {
+        ChainStatus::Failed(e) => assert!(e.contains("signature"), "{}", e),
+        status => panic!("chain {:?} against the wrong CA", status),
+    }
+    match chain(&self::ca("other CA").0, &client) {
+        ChainStatus::Failed(e) => assert!(e.contains("issuer certificate"), "{}", e),
+        status => panic!("chain {:?} against the wrong CA", status),
+    }
+}
@@ -0,0 +1,107 @@
+use chrono::{DateTime, Duration, SecondsFormat, TimeZone, Utc};
+use oxidizr::schema;
+use oxidizr::sqlstorage::SQLStorage;
+use oxidizr::tuf_expiry::{ExpiryChecker, ExpiryStatus};
+use oxidizr::tuf_repository_type::RepositoryType;
+use oxidizr::tuf_roles::Role;
+use rusqlite::{params, Connection};
+use tempfile::TempDir;
+
+fn metadata(kind: &str, expires: DateTime<Utc>) -> String {
+    let mut signed = serde_json::json!({
+        "_type": kind,
+        "version": 1,
+        "expires": expires.to_rfc3339_opts(SecondsFormat::Secs, true),
+    });
+    match kind {
+        "Root" => {
+            signed["keys"] = serde_json::json!({});
+            signed["roles"] = serde_json::json!({});
+        }
+        "Targets" => signed["targets"] = serde_json::json!({}),
+        _ => signed["meta"] = serde_json::json!({}),
+    }
+    serde_json::json!({ "signatures": [], "signed": signed }).to_string()
+}
+
+// Status of each Image repository role with its expiry date, checked at now with the window
+fn image_statuses(
+    expiries: [DateTime<Utc>; 4],
+    now: DateTime<Utc>,
+    window: Duration,
+) -> Vec<(Role, ExpiryStatus)> {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("sql.db");
+    let conn = Connection::open(&path).unwrap();
+    schema::create_unversioned(&conn).unwrap();
+    // Meta types of root, timestamp, snapshot and targets
+    for ((meta_type, kind), expires) in [
+        (0, "Root"),
+        (3, "Timestamp"),
+        (1, "Snapshot"),
+        (2, "Targets"),
+    ]
+    .into_iter()
+    .zip(expiries)
+    {
+        conn.execute(
+            "INSERT INTO meta(meta, repo, meta_type, version) VALUES (?, 0, ?, 1);",
+            params![metadata(kind, expires).into_bytes(), meta_type],
+        )
+        .unwrap();
+    }
+
+    let storage = SQLStorage::new(&path, false).unwrap();
+    ExpiryChecker::at(&storage, now, window)
+        .check_all()
+        .unwrap()
+        .into_iter()
+        .filter(|entry| entry.repo == RepositoryType::image())
+        .map(|entry| (entry.role, entry.status))
+        .collect()
+}
+
+#[test]
+fn status_changes_at_the_window_boundaries() {
+    let now = Utc.with_ymd_and_hms(2030, 6, 1, 0, 0, 0).unwrap();
+    let window = Duration::days(30);
+    let second = Duration::seconds(1);
+
+    let statuses = image_statuses(
+        [now, now + second, now + window, now + window + second],
+        now,
+        window,
+    );
+
+    assert_eq!(
+        statuses,
+        [
+            (Role::root(), ExpiryStatus::Expired),
+            (Role::timestamp(), ExpiryStatus::ExpiringSoon),
+            (Role::snapshot(), ExpiryStatus::ExpiringSoon),
+            (Role::targets(), ExpiryStatus::Valid),
+        ]
+    );
+}
+
+#[test]
+fn empty_window_reports_nothing_as_expiring_soon() {
+    let now = Utc.with_ymd_and_hms(2030, 6, 1, 0, 0, 0).unwrap();
+    let second = Duration::seconds(1);
+
+    let statuses = image_statuses(
+        [now - second, now, now + second, now + Duration::days(1)],
+        now,
+        Duration::zero(),
+    );
+
+    assert_eq!(
+        statuses,
+        [
+            (Role::root(), ExpiryStatus::Expired),
+            (Role::timestamp(), ExpiryStatus::Expired),
+            (Role::snapshot(), ExpiryStatus::Valid),
+            (Role::targets(), ExpiryStatus::Valid),
+        ]
+    );
+}